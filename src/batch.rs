@@ -0,0 +1,54 @@
+use crate::{BlockBlastPuzzle, Solution, SolveError, SolveOptions, solve_top_k, solve_with};
+use tracing::*;
+use core::iter::repeat_with;
+use core::num::NonZeroUsize;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
+use std::panic;
+use std::thread;
+use std::time::Instant;
+/// Result of one puzzle of a batch.
+#[derive(Clone, Debug)]
+pub struct Outcome {
+    /// The best solution, or the best few with a `top_k`.
+    pub result: Result<Vec<Solution>, SolveError>,
+    /// Time taken by the solve alone.
+    pub elapsed: Duration,
+}
+/// Solves every puzzle, `jobs` at a time (0 for one per core), each one as by [`solve_with`] or,
+/// with a `top_k`, by [`solve_top_k`].
+///
+/// Puzzles are handed out in order to whichever job is free, and the outcomes are returned in the
+/// order of `puzzles` whatever the number of jobs.
+#[instrument(skip_all)]
+#[must_use]
+pub fn solve(puzzles: &[BlockBlastPuzzle], options: &SolveOptions, top_k: Option<usize>, jobs: usize) -> Vec<Outcome> {
+    let workers = if jobs == 0 {
+        thread::available_parallelism().map_or(1, NonZeroUsize::get)
+    } else {
+        jobs
+    };
+    debug!(puzzles = puzzles.len(), ?workers);
+    let timed = |puzzle: &BlockBlastPuzzle| {
+        let start = Instant::now();
+        let result = top_k.map_or_else(|| solve_with(puzzle, options).map(|solution| vec![solution]), |k| solve_top_k(puzzle, options, k));
+        Outcome { result, elapsed: start.elapsed() }
+    };
+    let next = AtomicUsize::new(0);
+    let done: Vec<Vec<(usize, Outcome)>> = thread::scope(|scope| {
+        let handles: Vec<_> = repeat_with(|| scope.spawn(|| {
+            let mut outcomes = Vec::new();
+            loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(puzzle) = puzzles.get(index) else {
+                    return outcomes;
+                };
+                outcomes.push((index, timed(puzzle)));
+            }
+        })).take(workers.min(puzzles.len())).collect();
+        handles.into_iter().map(|handle| handle.join().unwrap_or_else(|e| panic::resume_unwind(e))).collect()
+    });
+    let mut outcomes: Vec<(usize, Outcome)> = done.into_iter().flatten().collect();
+    outcomes.sort_unstable_by_key(|&(index, _)| index);
+    outcomes.into_iter().map(|(_, outcome)| outcome).collect()
+}
@@ -0,0 +1,226 @@
+use crate::{Available, Chunk, Resolution, Rules};
+use crate::board::{Board, completable};
+use ndarray::prelude::*;
+/// Row-major cell bits, `W` words of 64 cells each.
+pub type Bits<const W: usize> = [u64; W];
+const fn bit(cols: usize, r: usize, c: usize) -> (usize, u64) {
+    let idx = r * cols + c;
+    (idx / 64, 1_u64 << (idx % 64))
+}
+#[expect(clippy::as_conversions, reason = "a popcount of at most 256 bits always fits in usize")]
+fn missing<const W: usize>(line: &Bits<W>, state: &Bits<W>) -> usize {
+    line.iter().zip(state).map(|(x, y)| (x & !y).count_ones()).sum::<u32>() as usize
+}
+fn overlaps<const W: usize>(a: &Bits<W>, b: &Bits<W>) -> bool {
+    a.iter().zip(b).any(|(x, y)| x & y != 0)
+}
+fn covers<const W: usize>(a: &Bits<W>, b: &Bits<W>) -> bool {
+    a.iter().zip(b).all(|(x, y)| x & y == *y)
+}
+/// Bitboard for boards of up to `64 * W` cells: one `u64` for the standard 8x8 board, four for
+/// 16x16. Pieces are pre-shifted to every anchor, so placing and clearing are plain mask compares.
+/// Void cells stay set in every state.
+#[derive(Clone, Debug)]
+pub struct BitBoard<const W: usize> {
+    rows: usize,
+    cols: usize,
+    // placements[piece][pos]
+    placements: Vec<Vec<Bits<W>>>,
+    void: Bits<W>,
+    // rows, columns, chunks, then regions, leaving out those made of void cells only
+    lines: Vec<Bits<W>>,
+    // (end, len) of each run of disjoint lines in `lines`
+    families: Vec<(usize, usize)>,
+    // End of each run of lines cleared at once, a single one unless cascading
+    stages: Vec<usize>,
+    missing: Vec<usize>,
+}
+impl<const W: usize> BitBoard<W> {
+    pub const fn fits(rows: usize, cols: usize) -> bool {
+        rows * cols <= 64 * W
+    }
+    pub fn new(rows: usize, cols: usize, pieces: &[Array2<bool>], piece_avail: &[Available], rules: &Rules) -> Self {
+        let placements = pieces.iter().zip(piece_avail).map(|(piece, &Available(avail_c, avail_len))| {
+            (0..avail_len).map(|pos| {
+                let (pos_r, pos_c) = (pos / avail_c, pos % avail_c);
+                let mut mask = [0_u64; W];
+                for ((i, j), k) in piece.indexed_iter() {
+                    if *k {
+                        let (w, b) = bit(cols, pos_r+i, pos_c+j);
+                        mask[w] |= b;
+                    }
+                }
+                mask
+            }).collect()
+        }).collect();
+        let mask_of = |cells: &mut dyn Iterator<Item = (usize, usize)>| {
+            let mut mask = [0_u64; W];
+            for (r, c) in cells {
+                let (w, b) = bit(cols, r, c);
+                mask[w] |= b;
+            }
+            mask
+        };
+        let void_bits = rules.void.as_ref().map_or([0_u64; W], |grid| mask_of(&mut grid.indexed_iter().filter(|&(_, &dead)| dead).map(|(cell, _)| cell)));
+        // (stage, lines) of every family, stages being rows, columns, chunks then regions
+        let mut candidates: Vec<(usize, Vec<Bits<W>>)> = Vec::new();
+        if rules.clear_rows {
+            candidates.push((0, (0..rows).map(|r| mask_of(&mut (0..cols).map(|c| (r, c)))).collect()));
+        }
+        if rules.clear_cols {
+            candidates.push((1, (0..cols).map(|c| mask_of(&mut (0..rows).map(|r| (r, c)))).collect()));
+        }
+        if rules.clear_regions {
+            if let Some(Chunk(chunk_r, chunk_c)) = rules.chunk {
+                candidates.push((2, (0..rows).step_by(chunk_r).flat_map(|r| (0..cols).step_by(chunk_c).map(move |c| (r, c)))
+                    .map(|(r, c)| mask_of(&mut (0..chunk_r*chunk_c).map(|k| (r + k / chunk_c, c + k % chunk_c)))).collect()));
+            }
+            // Regions may overlap, so each one is a family of its own
+            candidates.extend(rules.regions.iter().map(|region| (3, vec![mask_of(&mut region.cells.iter().copied())])));
+        }
+        let mut lines: Vec<Bits<W>> = Vec::with_capacity(rows + cols);
+        let mut families: Vec<(usize, usize)> = Vec::with_capacity(candidates.len());
+        let mut stages: Vec<usize> = Vec::with_capacity(4);
+        for (stage, family) in candidates {
+            // Completing a line again takes at least as many cells as its shortest one
+            let live: Vec<Bits<W>> = family.into_iter().filter(|line| missing(line, &void_bits) > 0).collect();
+            if let Some(len) = live.iter().map(|line| missing(line, &void_bits)).min() {
+                if rules.resolution == Resolution::Cascading && stages.len() <= stage {
+                    stages.resize(stage + 1, lines.len());
+                }
+                lines.extend(live);
+                families.push((lines.len(), len));
+                if let Some(end) = stages.last_mut() {
+                    *end = lines.len();
+                } else {
+                    stages.push(lines.len());
+                }
+            }
+        }
+        let missing = Vec::with_capacity(lines.len());
+        Self { rows, cols, placements, void: void_bits, lines, families, stages, missing }
+    }
+}
+impl<const W: usize> Board for BitBoard<W> {
+    type State = Bits<W>;
+    fn state_bytes(&self, _state: &Self::State) -> usize {
+        size_of::<Self::State>()
+    }
+    fn load(&self, grid: &Array2<bool>) -> Self::State {
+        let mut state = self.void;
+        for ((r, c), k) in grid.indexed_iter() {
+            if *k {
+                let (w, b) = bit(self.cols, r, c);
+                state[w] |= b;
+            }
+        }
+        state
+    }
+    fn store(&self, state: &Self::State) -> Array2<bool> {
+        Array::from_shape_fn((self.rows, self.cols), |(r, c)| {
+            let (w, b) = bit(self.cols, r, c);
+            state[w] & b != 0
+        })
+    }
+    fn place(&mut self, src: &Self::State, dst: &mut Self::State, piece: usize, pos: usize) -> Option<usize> {
+        let mask = &self.placements[piece][pos];
+        if overlaps(src, mask) {
+            return None;
+        }
+        let mut filled = *src;
+        for (x, y) in filled.iter_mut().zip(mask) {
+            *x |= y;
+        }
+        // Every line of a stage is checked against the board before any of them is cleared
+        let mut clear = 0_usize;
+        let mut start = 0_usize;
+        for &end in &self.stages {
+            let mut cleared = [0_u64; W];
+            for line in &self.lines[start..end] {
+                if covers(&filled, line) {
+                    clear += 1;
+                    for (x, y) in cleared.iter_mut().zip(line) {
+                        *x |= y;
+                    }
+                }
+            }
+            for (x, y) in filled.iter_mut().zip(cleared) {
+                *x &= !y;
+            }
+            start = end;
+        }
+        for ((d, x), v) in dst.iter_mut().zip(filled).zip(self.void) {
+            *d = x | v;
+        }
+        Some(clear)
+    }
+    fn clear_bound(&mut self, state: &Self::State, cells: usize) -> usize {
+        let mut bound = 0_usize;
+        let mut start = 0_usize;
+        for &(end, len) in &self.families {
+            self.missing.clear();
+            self.missing.extend(self.lines[start..end].iter().map(|line| missing(line, state)));
+            bound += completable(&mut self.missing, len, cells);
+            start = end;
+        }
+        bound
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::ArrayBoard;
+    use crate::catalogue::Catalogue;
+    use crate::rng::Rng;
+    /// Places every standard piece at every anchor of random `rows`x`cols` boards on both backends,
+    /// which must make the same clears and leave the same board, within their clear bound.
+    fn agree<const W: usize>(rows: usize, cols: usize, rules: &Rules) {
+        let pieces: Vec<Array2<bool>> = Catalogue::standard().entries.into_iter().map(|entry| entry.layout)
+            .filter(|layout| layout.nrows() <= rows && layout.ncols() <= cols).collect();
+        let piece_avail: Vec<Available> = pieces.iter().map(|layout| Available::new(rows, cols, layout.nrows(), layout.ncols())).collect();
+        let mut bits = BitBoard::<W>::new(rows, cols, &pieces, &piece_avail, rules);
+        let mut array = ArrayBoard::new(rows, cols, &pieces, &piece_avail, rules);
+        let mut rng = Rng::new(0);
+        let mut total = 0_usize;
+        // Denser boards have more lines one piece away from a clear
+        for density in [0, 50, 80, 95] {
+            let grid = Array::from_shape_simple_fn((rows, cols), || rng.below(100) < density);
+            let (bits_src, array_src) = (bits.load(&grid), array.load(&grid));
+            assert_eq!(bits.store(&bits_src), array.store(&array_src), "loaded boards differ at density {density}");
+            let (mut bits_dst, mut array_dst) = (bits_src, array_src.clone());
+            for (piece, layout) in pieces.iter().enumerate() {
+                let cells = layout.iter().filter(|&&k| k).count();
+                for pos in 0..piece_avail[piece].1 {
+                    let placed = bits.place(&bits_src, &mut bits_dst, piece, pos);
+                    assert_eq!(placed, array.place(&array_src, &mut array_dst, piece, pos), "clears of piece {piece} at {pos} differ");
+                    let Some(clears) = placed else {
+                        continue;
+                    };
+                    assert_eq!(bits.store(&bits_dst), array.store(&array_dst), "boards after piece {piece} at {pos} differ");
+                    assert!(clears <= bits.clear_bound(&bits_src, cells), "bitboard bound below {clears} clears of piece {piece} at {pos}");
+                    assert!(clears <= array.clear_bound(&array_src, cells), "ndarray bound below {clears} clears of piece {piece} at {pos}");
+                    total += clears;
+                }
+            }
+        }
+        assert!(total > 0, "no placement cleared anything");
+    }
+    #[test]
+    fn standard_board() {
+        agree::<1>(8, 8, &Rules::default());
+    }
+    #[test]
+    fn large_board() {
+        agree::<4>(16, 16, &Rules::default());
+    }
+    #[test]
+    fn void_cells() {
+        // A void row never clears, and the scattered void cells shorten the lines crossing them
+        let void = Array::from_shape_fn((8, 8), |(r, c)| r == 2 || (r + 2 * c) % 7 == 0);
+        agree::<1>(8, 8, &Rules { void: Some(void), ..Rules::default() });
+    }
+    #[test]
+    fn cascading_chunks() {
+        agree::<4>(9, 9, &Rules { resolution: Resolution::Cascading, ..Rules::woodoku() });
+    }
+}
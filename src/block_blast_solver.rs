@@ -1,271 +1,471 @@
+extern crate alloc;
 use mimalloc::MiMalloc;
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
-use clap::Parser;
+use clap::{Args as ClapArgs, Parser, Subcommand, ValueEnum};
 use tracing::*;
 use scan_rules::*;
+use scan_rules::scanner::Word;
 use tracing_subscriber::EnvFilter;
 use tracing_subscriber::fmt::time::Uptime;
 use tracing_subscriber::fmt::format::FmtSpan;
 use ndarray::prelude::*;
-use std::io::{BufRead as _, stdin, IsTerminal as _};
-#[derive(Copy, Clone, Debug)]
-struct Chunk(usize, usize);
-#[derive(Copy, Clone, Debug)]
-struct Available(usize, usize);
-impl Available {
-    const fn new(rows: usize, cols: usize, r: usize, c: usize) -> Self {
-        let avail_r = rows - r + 1;
-        let avail_c = cols - c + 1;
-        let avail_len = avail_r * avail_c;
-        Self(avail_c, avail_len)
-    }
+use block_blast_solver::{Backend, BlockBlastPuzzle, CancelToken, Chunk, Placement, Region, Resolution, Rules, Solution, SolveOptions, solve_top_k, solve_with};
+use block_blast_solver::scoring::{Combo, LinesCleared, Points, Scoring};
+use block_blast_solver::eval::Weights;
+use block_blast_solver::catalogue::Catalogue;
+use block_blast_solver::simulate::{Greedy, Simulation, Spread, Strategy, simulate};
+use block_blast_solver::lookahead::Lookahead;
+use alloc::sync::Arc;
+use block_blast_solver::screenshot::{self, Calibration, Rect};
+use std::fs::{self, File};
+use block_blast_solver::json::{AnalysisDoc, PuzzleDoc, RecordDoc, SolutionDoc, SummaryDoc, SCHEMA_VERSION};
+use block_blast_solver::batch::{self, Outcome};
+use block_blast_solver::piece;
+use block_blast_solver::render;
+use block_blast_solver::risk::{self, Analysis};
+use block_blast_solver::generate::{Constraint, Generation, generate};
+use block_blast_solver::SolveError;
+use core::time::Duration;
+use std::{env, process::{self, ExitCode}};
+use std::time::Instant;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use std::io::{self, BufRead as _, BufReader, Read as _, stdin, IsTerminal as _};
+use std::path::{Path, PathBuf};
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum ScoringArg {
+    /// Lines (and chunks) cleared.
+    Lines,
+    /// In-game points with combo multipliers.
+    Points,
+}
+/// A piece given by its dimensions, with the layout on the following lines, or by name.
+enum PieceInput {
+    Dims(usize, usize),
+    Name(String),
+}
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Preset {
+    /// Rows and columns, as in Block Blast! and 1010!.
+    BlockBlast,
+    /// Rows, columns and 3x3 chunks, as in Woodoku.
+    Woodoku,
+    /// Rows only, as in Tetris-like games.
+    RowsOnly,
+}
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum ColorArg {
+    /// Colours when stdout is a terminal and `NO_COLOR` is not set.
+    Auto,
+    Always,
+    Never,
+}
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// The interactive prompts, or grids drawn with `#` and `.`.
+    Text,
+    /// Versioned JSON documents.
+    Json,
 }
 #[derive(Parser, Debug, Clone)]
+#[expect(clippy::struct_excessive_bools, reason = "independent command line switches")]
 #[command(version, about = "Block Blast! solver written in Rust", long_about = None)]
 struct Args {
     #[arg(short, long, default_value_t = false, help = "Also clears subgrids/\"chunks\" with sizes specified in the program")]
     wooden: bool,
     #[arg(short, long, default_value_t = false, help = "Use Steinhaus-Johnson-Trotter algorithm for permutations instead")]
     sjt: bool,
+    #[arg(short, long, value_enum, default_value_t = Backend::Auto, help = "Board representation used by the search")]
+    backend: Backend,
+    #[arg(long, value_enum, default_value_t = ScoringArg::Lines, help = "Objective to maximize")]
+    scoring: ScoringArg,
+    #[arg(short, long, default_value_t = false, help = "Rank solutions of equal score by how playable their final board is")]
+    tiebreak: bool,
+    #[arg(long, value_name = "HOLES,ROUGHNESS,RECTANGLE,FITS", help = "Tie-breaker weights of the final board features, implies --tiebreak")]
+    weights: Option<Weights>,
+    #[arg(short, long, default_value_t = false, help = "Prefer solutions that leave room for the next round of pieces")]
+    lookahead: bool,
+    #[arg(long, default_value_t = 32, help = "Next deals sampled per candidate with --lookahead, 0 to enumerate every deal")]
+    samples: usize,
+    #[arg(long, default_value_t = 8, help = "Best solutions compared with --lookahead")]
+    candidates: usize,
+    #[arg(long, default_value_t = 0, help = "Seed of the deals sampled with --lookahead")]
+    seed: u64,
+    #[arg(long, default_value_t = 100, help = "Score lost when a next deal cannot be placed with --lookahead")]
+    penalty: usize,
+    #[arg(short = 'j', long, default_value_t = 1, help = "Worker threads of the search, 0 for one per core")]
+    threads: usize,
+    #[arg(long, value_name = "MIB", default_value_t = 64, help = "Memory cap of the transposition table, 0 to disable it")]
+    table_mib: usize,
+    #[arg(long, global = true, value_name = "SECONDS", value_parser = parse_seconds, help = "Stop each search after this long and keep the best solution found so far")]
+    time_limit: Option<Duration>,
+    #[arg(long, global = true, value_name = "N", help = "Stop each search after trying this many placements and keep the best solution found so far")]
+    node_limit: Option<usize>,
+    #[arg(long, value_name = "PIECE@ROW,COL", value_parser = parse_placement, help = "Play this piece (from 1) at this anchor before searching the others, repeatable in order")]
+    fixed: Vec<Placement>,
+    #[arg(short = 'k', long, value_name = "N", help = "Print the N best solutions with distinct final boards")]
+    top_k: Option<usize>,
+    #[arg(long, global = true, value_name = "ROWS,COLS", value_parser = parse_chunk, help = "Chunk dimensions for --wooden instead of asking for them")]
+    chunk: Option<Chunk>,
+    #[arg(long, global = true, value_enum, default_value_t = Preset::BlockBlast, help = "Clearing rules of the game")]
+    rules: Preset,
+    #[arg(long, global = true, default_value_t = false, help = "Never clear rows")]
+    no_rows: bool,
+    #[arg(long, global = true, default_value_t = false, help = "Never clear columns")]
+    no_columns: bool,
+    #[arg(long, global = true, default_value_t = false, help = "Never clear chunks and regions")]
+    no_regions: bool,
+    #[arg(long, global = true, value_enum, help = "When completed lines are cleared [default: simultaneous]")]
+    resolution: Option<Resolution>,
+    #[arg(long, value_enum, default_value_t = Format::Text, help = "Format of the puzzle read from stdin")]
+    input_format: Format,
+    #[arg(long, value_enum, default_value_t = Format::Text, help = "Format of the solution written to stdout")]
+    output_format: Format,
+    #[arg(long, value_enum, default_value_t = ColorArg::Auto, help = "Show the steps side by side in colour, highlighting the placed piece and the cells it clears")]
+    color: ColorArg,
+    #[arg(long, value_name = "FILE", help = "Also draw the steps of the best solution to an .svg or .html file")]
+    export: Option<PathBuf>,
+    #[arg(long, value_name = "PNG", help = "Read the board and pieces from a screenshot instead of stdin")]
+    image: Option<PathBuf>,
+    #[arg(long, value_name = "ROWS,COLS", value_parser = parse_chunk, help = "Board cells of the screenshot [default: 8,8, 9,9 with --rules woodoku]")]
+    cells: Option<Chunk>,
+    #[arg(long, value_name = "X,Y,WIDTH,HEIGHT", help = "Board area of the screenshot instead of detecting it")]
+    grid: Option<Rect>,
+    #[arg(long, value_name = "X,Y,WIDTH,HEIGHT", help = "Area of the screenshot holding the pieces [default: below the board]")]
+    tray: Option<Rect>,
+    #[arg(long, help = "Brightest channel value from which a screenshot pixel is part of a block [default: 160]")]
+    threshold: Option<u8>,
+    #[arg(long, help = "Colour difference from the background that makes a screenshot pixel part of the board [default: 48]")]
+    tolerance: Option<u32>,
+    #[arg(long, value_name = "PERCENT", help = "Cell size of the offered pieces relative to the board cells [default: 50]")]
+    piece_scale: Option<usize>,
+    #[command(subcommand)]
+    command: Option<Command>,
 }
-#[derive(Debug)]
-struct Permutation {
-    perm: Vec<usize>,
-    pos: Vec<usize>,
-    dir: Vec<bool>,
-    n: usize,
-    sjt: bool,
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Play whole games with random deals and report how long they last.
+    Simulate(SimulateArgs),
+    /// Solve many puzzles from one file or stream and summarize the results.
+    Batch(BatchArgs),
+    /// Tell how dangerous the board read from stdin is, its pieces being optional and ignored.
+    Analyze(AnalyzeArgs),
+    /// Write random puzzles with a given property in the text input format, one after the other.
+    Generate(GenerateArgs),
 }
-impl Permutation {
-    fn new(n: usize, sjt: bool) -> Self {
-        let perm: Vec<usize> = (0..n).collect();
-        let mut pos: Vec<usize> = vec![0; n];
-        let dir: Vec<bool> = vec![false; n];
-        if sjt {
-            pos = (0..n).collect();
-        }
-        Self { perm, pos, dir, n, sjt }
-    }
-    // Steinhaus-Johnson-Trotter algorithm (Even's speedup)
-    // https://en.wikipedia.org/wiki/Steinhaus%E2%80%93Johnson%E2%80%93Trotter_algorithm#Even's_speedup
-    fn permute_sjt(&mut self) -> bool {
-        let mut k = self.n - 1;
-        while k > 0 {
-            let c_idx = self.pos[k];
-            let target_idx = if self.dir[k] {
-                let next = c_idx + 1;
-                (next < self.n).then_some(next)
-            } else {
-                c_idx.checked_sub(1)
-            };
-            if let Some(t_idx) = target_idx && self.perm[t_idx] < k {
-                let neighbor_val = self.perm[t_idx];
-                self.perm.swap(c_idx, t_idx);
-                self.pos[k] = t_idx;
-                self.pos[neighbor_val] = c_idx;
-                debug!(l = ?c_idx, r = ?t_idx, perm = ?self.perm, pos = ?self.pos, dir = ?self.dir);
-                return true;
-            }
-            self.dir[k] = !self.dir[k];
-            k -= 1;
-        }
-        false
-    }
-    // Heap's algorithm (non-recursive)
-    // https://en.wikipedia.org/wiki/Heap%27s_algorithm#cite_ref-3
-    fn permute_heap(&mut self) -> bool {
-        let mut i = 1_usize;
-        while i < self.n {
-            if self.pos[i] < i {
-                let l = if (i&1)==0 {0_usize} else {self.pos[i]};
-                self.perm.swap(l, i);
-                self.pos[i] += 1;
-                debug!(?l, r = ?i, perm = ?self.perm, c = ?self.pos);
-                return true;
-            }
-            self.pos[i] = 0;
-            i += 1;
-        }
-        false
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum StrategyArg {
+    /// Best placement sequence of the whole deal.
+    Search,
+    /// Best position of each piece alone, in the order dealt.
+    Greedy,
+}
+#[derive(ClapArgs, Debug, Clone)]
+struct SimulateArgs {
+    #[arg(short, long, default_value_t = 100, help = "Games to play")]
+    games: usize,
+    #[arg(long, default_value_t = 0, help = "Seed of the dealt pieces")]
+    seed: u64,
+    #[arg(long, help = "Board rows [default: 8, 9 with --rules woodoku]")]
+    rows: Option<usize>,
+    #[arg(long, help = "Board columns [default: 8, 9 with --rules woodoku]")]
+    cols: Option<usize>,
+    #[arg(long, default_value_t = 3, help = "Pieces per deal")]
+    deal: usize,
+    #[arg(long, help = "Stop every game after this many rounds")]
+    max_rounds: Option<usize>,
+    #[arg(long, value_enum, default_value_t = StrategyArg::Search, help = "How the moves of a round are chosen")]
+    strategy: StrategyArg,
+    #[arg(short, long, default_value_t = false, help = "Print the outcome of every game")]
+    verbose: bool,
+}
+#[derive(ClapArgs, Debug, Clone)]
+struct BatchArgs {
+    #[arg(value_name = "FILE", help = "Puzzles in --input-format, one after the other (one per line in JSON), stdin when missing or -")]
+    input: Option<PathBuf>,
+    #[arg(long, default_value_t = 0, help = "Puzzles solved at once, 0 for one per core")]
+    jobs: usize,
+}
+#[derive(ClapArgs, Debug, Clone)]
+struct AnalyzeArgs {
+    #[arg(long, default_value_t = 3, help = "Pieces per deal")]
+    deal: usize,
+    #[arg(long, default_value_t = 0, help = "Deals sampled, 0 to try every distinct deal")]
+    samples: usize,
+    #[arg(long, default_value_t = 0, help = "Seed of the sampled deals")]
+    seed: u64,
+    #[arg(long, default_value_t = 0, help = "Deals checked at once, 0 for one per core")]
+    jobs: usize,
+}
+#[derive(ClapArgs, Debug, Clone)]
+struct GenerateArgs {
+    #[arg(short = 'n', long, default_value_t = 10, help = "Puzzles to write")]
+    count: usize,
+    #[arg(long, default_value = "solvable", value_name = "solvable|unsolvable|clears=N|unique-order|near-miss", help = "Property every puzzle must have")]
+    constraint: Constraint,
+    #[arg(long, default_value_t = 0, help = "Seed of the boards and deals")]
+    seed: u64,
+    #[arg(long, help = "Board rows [default: 8, 9 with --rules woodoku]")]
+    rows: Option<usize>,
+    #[arg(long, help = "Board columns [default: 8, 9 with --rules woodoku]")]
+    cols: Option<usize>,
+    #[arg(long, default_value_t = 3, help = "Pieces per deal")]
+    deal: usize,
+    #[arg(long, value_name = "PERCENT", default_value_t = 40, help = "Chance of every board cell to start filled")]
+    density: u64,
+    #[arg(long, default_value_t = 10_000, help = "Random candidates tried before giving up")]
+    tries: usize,
+}
+fn parse_chunk(s: &str) -> Result<Chunk, String> {
+    match s.split_once(',').map(|(r, c)| (r.trim().parse::<usize>(), c.trim().parse::<usize>())) {
+        Some((Ok(r), Ok(c))) => Ok(Chunk(r, c)),
+        Some((Err(e), _) | (_, Err(e))) => Err(e.to_string()),
+        None => Err(format!("expected ROWS,COLS, got {s:?}")),
     }
-    fn permute(&mut self) -> bool {
-        if self.sjt {
-            self.permute_sjt()
-        } else {
-            self.permute_heap()
-        }
+}
+fn parse_placement(s: &str) -> Result<Placement, String> {
+    let Some((piece, anchor)) = s.split_once('@') else {
+        return Err(format!("expected PIECE@ROW,COL, got {s:?}"));
+    };
+    match (piece.trim().parse::<usize>(), parse_chunk(anchor)) {
+        (Ok(0), _) => Err("pieces are numbered from 1".to_owned()),
+        (Ok(n), Ok(Chunk(row, col))) => Ok(Placement { piece: n - 1, row, col }),
+        (Err(e), _) => Err(e.to_string()),
+        (_, Err(e)) => Err(e),
+    }
+}
+fn parse_seconds(s: &str) -> Result<Duration, String> {
+    match s.trim().parse::<f64>() {
+        Ok(secs) => Duration::try_from_secs_f64(secs).map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
     }
 }
-fn print_grid(grid: &ArrayView2<bool>) {
+fn print_grid(grid: &ArrayView2<bool>, void: Option<&Array2<bool>>) {
     let (r, c) = grid.dim();
     for i in 0..r {
         for j in 0..c {
-            print!("{}", if grid[[i, j]] {'#'} else {'.'});
+            let dead = void.is_some_and(|mask| mask[[i, j]]);
+            print!("{}", if dead {'X'} else if grid[[i, j]] {'#'} else {'.'});
         }
         println!();
     }
 }
-#[instrument(skip_all)]
-fn solve(place_order: &mut Vec<usize>, piece_order: &mut Vec<usize>, lines_cleared: &mut Vec<usize>, pieces: &[Array2<bool>], piece_avail: &[Available], state: &mut Array3<bool>, sjt: bool, chunk: Option<Chunk>) -> Option<usize> {
-    let (_, rows, cols) = state.dim();
-    let n_pieces = pieces.len();
-    let wooden = chunk.is_some();
-    let (chunk_r, chunk_c) = match chunk {
-        Some(Chunk(r, c)) => (r, c),
-        None => (0, 0),
-    };
-    let (mut placed_pieces, mut total_lines_cleared, mut max_lines_cleared) = (0_usize, 0_usize, 0_usize);
-    let mut working_place_order = place_order.clone();
-    let mut working_lines_cleared = lines_cleared.clone();
-    let mut working_state = state.clone();
-    let mut piece_perm = Permutation::new(n_pieces, sjt);
-    let mut row_filled: Vec<bool> = vec![false; rows];
-    let mut col_filled: Vec<bool> = vec![false; cols];
-    let mut solvable = false;
-    debug!(?n_pieces, ?rows, ?cols, ?piece_avail);
-    trace!(?working_place_order, ?working_lines_cleared, ?working_state, ?pieces);
-    let mut place = |placed_pieces: usize, idx: usize, pos_r: usize, pos_c: usize, working_state: &mut Array3<bool>| -> Option<usize> {
-        let piece = &pieces[idx];
-        for ((i, j), k) in piece.indexed_iter() {
-            if *k && working_state[[placed_pieces, pos_r+i, pos_c+j]] {
-                return None;
-            }
-        }
-        let mut clear = 0_usize;
-        let (mut wstate, src) = working_state.multi_slice_mut((s![placed_pieces+1, .., ..], s![placed_pieces, .., ..]));
-        if let Some(d_raw) = wstate.as_slice_mut() &&
-           let Some(s_raw) = src.as_slice() {
-            d_raw.copy_from_slice(s_raw);
+/// Filled and void cells of a board whose void cells are drawn with `X`, no void without any.
+fn board_cells(cells: &Array2<char>) -> (Array2<bool>, Option<Array2<bool>>) {
+    let board = cells.mapv(|ch| ch != '.' && ch != 'X');
+    let void = cells.iter().any(|&ch| ch == 'X').then(|| cells.mapv(|ch| ch == 'X'));
+    (board, void)
+}
+fn print_solution(puzzle: &BlockBlastPuzzle, solution: &Solution, scoring: ScoringArg, color: bool) {
+    let rules = &puzzle.rules;
+    let frames = if color {render::frames(puzzle, solution)} else {Vec::new()};
+    for (i, step) in solution.steps.iter().enumerate() {
+        let fixed = if step.fixed {" (fixed)"} else {""};
+        println!("Piece {}: {} {}{fixed}", step.piece+1, step.row, step.col);
+        if let Some(frame) = frames.get(i) {
+            print!("{}", render::ansi(frame, rules.void.as_ref()));
         } else {
-            wstate.assign(&src);
-        }
-        for ((i, j), k) in piece.indexed_iter() {
-            if *k {
-                wstate[[pos_r+i, pos_c+j]] = true;
-            }
+            print_grid(&step.board.view(), rules.void.as_ref());
         }
-        for (i, row) in wstate.axis_iter(Axis(0)).enumerate() {
-            row_filled[i] = row.iter().all(|&x| x);
+        let score = step.score;
+        match (step.clears - step.regions.len(), scoring) {
+            (0, ScoringArg::Lines) => {},
+            (1, ScoringArg::Lines) => println!("(1 clear)"),
+            (c, ScoringArg::Lines) => println!("({c} clears)"),
+            (0, ScoringArg::Points) => println!("+{score}"),
+            (1, ScoringArg::Points) => println!("(1 clear) +{score}"),
+            (c, ScoringArg::Points) => println!("({c} clears) +{score}"),
         }
-        for (i, col) in wstate.axis_iter(Axis(1)).enumerate() {
-            col_filled[i] = col.iter().all(|&x| x);
+        if !step.regions.is_empty() {
+            let names: Vec<&str> = step.regions.iter().map(|&region| rules.regions[region].name.as_str()).collect();
+            println!("Regions cleared: {}", names.join(" "));
         }
-        if wooden {
-            for mut chunk in wstate.exact_chunks_mut((chunk_r, chunk_c)) {
-                if chunk.iter().all(|&x| x) {
-                    clear += 1;
-                    chunk.fill(false);
-                }
-            }
-        }
-        for (i, mut row) in wstate.axis_iter_mut(Axis(0)).enumerate() {
-            if row_filled[i] {
-                clear += 1;
-                row.fill(false);
-            }
-        }
-        for (i, mut col) in wstate.axis_iter_mut(Axis(1)).enumerate() {
-            if col_filled[i] {
-                clear += 1;
-                col.fill(false);
-            }
+    }
+    if let Some(outlook) = solution.outlook && outlook.deals > 0 {
+        // Permille to print one decimal without floats
+        let placeable = outlook.placeable * 1000 / outlook.deals;
+        let next_score = outlook.next_score * 10 / outlook.deals;
+        println!("Next round: {}.{}% placeable, {}.{} expected score", placeable / 10, placeable % 10, next_score / 10, next_score % 10);
+    }
+    if !solution.optimal {
+        println!("Not proven optimal");
+    }
+}
+/// Writes the steps of `solution` as an SVG image or an HTML page, by the extension of `path`.
+fn export(path: &Path, puzzle: &BlockBlastPuzzle, solution: &Solution) {
+    let document = match path.extension().and_then(|ext| ext.to_str()).map(str::to_ascii_lowercase).as_deref() {
+        Some("svg") => render::svg(puzzle, solution),
+        Some("html" | "htm") => render::html(puzzle, solution),
+        _ => {
+            error!(?path, "Cannot tell the export format, use an .svg or .html file");
+            return;
+        },
+    };
+    match fs::write(path, document) {
+        Ok(()) => info!(?path, "Solution exported"),
+        Err(e) => error!(error = %e, ?path, "Cannot write the export"),
+    }
+}
+fn solve_options(args: &Args) -> SolveOptions {
+    let scoring: Arc<dyn Scoring> = match args.scoring {
+        ScoringArg::Lines => Arc::new(LinesCleared),
+        ScoringArg::Points => Arc::new(Points::default()),
+    };
+    let tiebreak = args.weights.or_else(|| args.tiebreak.then(Weights::default));
+    let lookahead = args.lookahead.then(|| Lookahead {
+        samples: (args.samples > 0).then_some(args.samples),
+        seed: args.seed,
+        candidates: args.candidates,
+        penalty: args.penalty,
+        ..Lookahead::default()
+    });
+    SolveOptions {
+        sjt: args.sjt,
+        backend: args.backend,
+        scoring,
+        tiebreak,
+        lookahead,
+        threads: args.threads,
+        table_bytes: args.table_mib << 20,
+        time_limit: args.time_limit,
+        node_limit: args.node_limit,
+        cancel: CancelToken::default(),
+    }
+}
+/// Makes Ctrl-C stop the searches of `cancel` cleanly, and a second one exit at once.
+#[expect(clippy::exit, reason = "a second Ctrl-C must not wait for the search to wind down")]
+fn cancel_on_interrupt(cancel: &CancelToken) {
+    let token = cancel.clone();
+    let handled = ctrlc::set_handler(move || {
+        if token.is_cancelled() {
+            process::exit(130);
         }
-        Some(clear)
-    };
-    let mut blast = |working_piece_order: &Vec<usize>| {
-        'outer: loop {
-            {
-                let piece_idx = working_piece_order[placed_pieces];
-                let pos = &mut working_place_order[placed_pieces];
-                let Available(avail_c, avail_len) = piece_avail[piece_idx];
-                loop {
-                    if *pos == avail_len {
-                        *pos = 0;
-                        if placed_pieces == 0 {
-                            return;
-                        }
-                        placed_pieces -= 1;
-                        total_lines_cleared -= working_lines_cleared[placed_pieces];
-                        working_place_order[placed_pieces] += 1;
-                        continue 'outer;
-                    }
-                    if let Some(clear) = place(placed_pieces, piece_idx, *pos / avail_c, *pos % avail_c, &mut working_state) {
-                        working_lines_cleared[placed_pieces] = clear;
-                        total_lines_cleared += clear;
-                        break;
-                    }
-                    *pos += 1;
-                }
-            }
-            if placed_pieces + 1 == n_pieces {
-                if !solvable {solvable = true;}
-                if total_lines_cleared > max_lines_cleared {
-                    max_lines_cleared = total_lines_cleared;
-                    place_order.clone_from(&working_place_order);
-                    piece_order.clone_from(working_piece_order);
-                    lines_cleared.clone_from(&working_lines_cleared);
-                    state.clone_from(&working_state);
-                }
-                total_lines_cleared -= working_lines_cleared[placed_pieces];
-                working_place_order[placed_pieces] += 1;
-                continue;
-            }
-            placed_pieces += 1;
+        warn!("Interrupted, stopping the search; press Ctrl-C again to quit");
+        token.cancel();
+    });
+    if let Err(e) = handled {
+        warn!(error = %e, "Cannot handle Ctrl-C");
+    }
+}
+/// Sets the clearing rules of `--rules` and the switches refining it, keeping the chunk, void
+/// cells and regions of the puzzle.
+fn apply_rules(args: &Args, rules: &mut Rules) {
+    let preset = match args.rules {
+        Preset::BlockBlast => Rules::default(),
+        Preset::Woodoku => Rules::woodoku(),
+        Preset::RowsOnly => Rules::rows_only(),
+    };
+    rules.clear_rows = preset.clear_rows && !args.no_rows;
+    rules.clear_cols = preset.clear_cols && !args.no_columns;
+    rules.clear_regions = preset.clear_regions && !args.no_regions;
+    rules.resolution = args.resolution.unwrap_or(preset.resolution);
+    if rules.chunk.is_none() {
+        rules.chunk = preset.chunk;
+    }
+}
+/// Rows and columns of the board of `--rules` when none are given, as its 3x3 chunks only fit
+/// the 9x9 grid of Woodoku.
+fn board_size(args: &Args) -> usize {
+    if args.rules == Preset::Woodoku {9} else {8}
+}
+/// Warns about the pieces of `puzzle` whose cells are not connected, most likely mistyped.
+fn check_pieces(puzzle: &BlockBlastPuzzle) {
+    for (i, layout) in puzzle.pieces.iter().enumerate() {
+        if !piece::connected(layout) {
+            warn!(piece = i+1, "Piece cells are not connected, solving it as given");
         }
+    }
+}
+fn print_spread(name: &str, spread: &Spread, games: usize) {
+    // Hundredths to print the mean without floats
+    let mean = spread.total * 100 / games.max(1);
+    println!("{name}: min {}, quartiles {} {} {}, max {}, mean {}.{:02}",
+             spread.min, spread.lower, spread.median, spread.upper, spread.max, mean / 100, mean % 100);
+}
+fn run_simulation(args: &Args, sim: &SimulateArgs) {
+    if args.wooden && args.chunk.is_none() {
+        error!("--wooden needs --chunk to simulate");
+        return;
+    }
+    let mut rules = Rules { chunk: args.chunk.filter(|_| args.wooden), ..Rules::default() };
+    apply_rules(args, &mut rules);
+    let simulation = Simulation {
+        rows: sim.rows.unwrap_or_else(|| board_size(args)),
+        cols: sim.cols.unwrap_or_else(|| board_size(args)),
+        rules,
+        catalogue: Catalogue::standard(),
+        deal: sim.deal,
+        games: sim.games,
+        seed: sim.seed,
+        max_rounds: sim.max_rounds,
     };
-    debug!("{:?}", piece_perm);
-    loop {
-        blast(&piece_perm.perm);
-        if !piece_perm.permute() {
-            break;
+    let options = solve_options(args);
+    cancel_on_interrupt(&options.cancel);
+    let strategy: Box<dyn Strategy> = match sim.strategy {
+        StrategyArg::Search => Box::new(options),
+        StrategyArg::Greedy => Box::new(Greedy(options)),
+    };
+    let games = match simulate(&simulation, &*strategy) {
+        Ok(games) => games,
+        Err(e) => {
+            error!(error = %e, "Simulation failed");
+            return;
+        },
+    };
+    if games.len() < simulation.games {
+        warn!(finished = games.len(), wanted = simulation.games, "Stopped early, reporting the finished games only");
+    }
+    if sim.verbose {
+        for (i, game) in games.iter().enumerate() {
+            let proven = if game.optimal {""} else {", not proven optimal"};
+            println!("Game {}: {} rounds, {} clears, {} score{proven}", i+1, game.rounds, game.clears, game.score);
         }
     }
-    solvable.then_some(max_lines_cleared)
+    println!("Games: {}", games.len());
+    let unproven = games.iter().filter(|game| !game.optimal).count();
+    if unproven > 0 {
+        println!("Not proven optimal: {unproven} games");
+    }
+    print_spread("Rounds", &Spread::of(games.iter().map(|game| game.rounds).collect()), games.len());
+    print_spread("Clears", &Spread::of(games.iter().map(|game| game.clears).collect()), games.len());
+    print_spread("Score", &Spread::of(games.iter().map(|game| game.score).collect()), games.len());
 }
-fn main() {
-    let args = Args::parse();
-    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
-    tracing_subscriber::fmt()
-        .with_env_filter(filter)
-        .with_target(false)
-        .with_line_number(true)
-        .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE)
-        .with_timer(Uptime::default())
-        .init();
-    let _main_span = info_span!("main").entered();
+/// Reads a puzzle in the interactive text format from stdin.
+fn read_puzzle(args: &Args) -> Option<BlockBlastPuzzle> {
     let term = stdin().is_terminal();
-    info!(terminal = ?term, ?args);
-    let (rows, cols, n_pieces) = loop {
+    // The pieces of an analyzed board are ignored, so there may be none
+    let analyzing = matches!(args.command, Some(Command::Analyze(_)));
+    let (rows, cols, n_pieces, n_regions) = loop {
         if term {
-            print!("Enter the grid dimensions (rows by columns) and the number of pieces: ");
+            print!("Enter the grid dimensions (rows by columns), the number of pieces and optionally of clear regions: ");
         }
         let result = try_readln! {
-            (let rows: usize, let cols: usize, let n_pieces: usize) => (rows, cols, n_pieces)
+            (let rows: usize, let cols: usize, let n_pieces: usize, let n_regions: usize) => (rows, cols, n_pieces, n_regions),
+            (let rows: usize, let cols: usize, let n_pieces: usize) => (rows, cols, n_pieces, 0),
         };
         match result {
-            Ok((rows, cols, n_pieces)) => {
-                if rows == 0 || cols == 0 || n_pieces == 0 {
+            Ok((rows, cols, n_pieces, n_regions)) => {
+                if rows == 0 || cols == 0 || (n_pieces == 0 && !analyzing) {
                     error!(?rows, ?cols, ?n_pieces, "Invalid input");
                     if !term {
-                        return;
+                        return None;
                     }
                 } else {
-                    break (rows, cols, n_pieces);
+                    break (rows, cols, n_pieces, n_regions);
                 }
             },
             Err(e) => {
                 error!(error = %e, "Invalid input");
                 if !term {
-                    return;
+                    return None;
                 }
             },
         }
     };
-    debug!(?rows, ?cols, ?n_pieces);
-    let mut chunk = Chunk(0, 0);
-    if args.wooden {
-        chunk = loop {
+    debug!(?rows, ?cols, ?n_pieces, ?n_regions);
+    let mut chunk = args.chunk.filter(|_| args.wooden);
+    if args.wooden && chunk.is_none() {
+        chunk = Some(loop {
             if term {
                 print!("Enter the chunk dimensions (rows by columns): ");
             }
@@ -282,7 +482,7 @@ fn main() {
                             error!(?rows, ?cols, ?r, ?c, "Chunks do not distribute over the grid evenly");
                         }
                         if !term {
-                            return;
+                            return None;
                         }
                     } else {
                         break Chunk(r, c);
@@ -291,77 +491,117 @@ fn main() {
                 Err(e) => {
                     error!(error = %e, "Invalid input");
                     if !term {
-                        return;
+                        return None;
                     }
                 },
             }
-        };
+        });
         debug!(?chunk);
     }
-    let mut place_order: Vec<usize> = vec![0; n_pieces];
-    let mut piece_order: Vec<usize> = vec![0; n_pieces];
-    let mut lines_cleared: Vec<usize> = vec![0; n_pieces];
     let mut pieces: Vec<Array2<bool>> = Vec::with_capacity(n_pieces);
-    let mut piece_avail: Vec<Available> = Vec::with_capacity(n_pieces);
-    let mut state: Array3<bool> = Array::from_elem((n_pieces+1, rows, cols), false);
+    let mut cells: Array2<char> = Array::from_elem((rows, cols), '.');
     if term {
         println!(r"Enter the grid layout row by row.
   - Use '.' for an empty cell.
+  - Use 'X' for a void cell, which can never be filled.
 Any other character will be interpreted as a filled cell.
 Row string input with insufficient length will leave the remaining cells empty.");
     }
     {
         let mut iterator = stdin().lock().lines();
         for i in 0..rows {
-            let row_str = iterator.next().unwrap().unwrap();
+            let Some(Ok(row_str)) = iterator.next() else {
+                error!("Unexpected end of input");
+                return None;
+            };
             for (j, c) in row_str.chars().enumerate() {
                 if j == cols {
                     break;
                 }
-                state[[0, i, j]] = c != '.';
+                cells[[i, j]] = c;
             }
         }
     }
-    info!("Enter the dimensions and layout for each pieces");
-    for i in 0..n_pieces {
-        let (r, c) = loop {
+    let (board, void) = board_cells(&cells);
+    let mut regions: Vec<Region> = Vec::with_capacity(n_regions);
+    if term && n_regions > 0 {
+        println!("Enter each clear region as its name followed by its cells as ROW,COL, separated by spaces.");
+    }
+    while regions.len() < n_regions {
+        if term {
+            print!("Region {}: ", regions.len()+1);
+        }
+        let Some(Ok(line)) = stdin().lock().lines().next() else {
+            error!("Unexpected end of input");
+            return None;
+        };
+        match line.parse::<Region>() {
+            Ok(region) => {
+                debug!(?region);
+                regions.push(region);
+            },
+            Err(e) => {
+                error!(error = %e, "Invalid region");
+                if !term {
+                    return None;
+                }
+            },
+        }
+    }
+    info!("Enter the dimensions and layout, or the catalogue name, for each pieces");
+    let catalogue = Catalogue::standard();
+    while pieces.len() < n_pieces {
+        let i = pieces.len();
+        let dims = loop {
             if term {
                 print!("Piece {}: ", i+1);
             }
             let result = try_readln! {
-                (let r: usize, let c: usize) => (r, c)
+                (let r: usize, let c: usize) => PieceInput::Dims(r, c),
+                (let name: Word<String>) => PieceInput::Name(name),
             };
             match result {
-                Ok((r, c)) => {
-                    let invalid = r == 0 || c == 0;
-                    if invalid || rows < r || cols < c {
-                        if invalid {
-                            error!(?r, ?c, "Invalid input");
-                        } else {
-                            error!(?rows, ?cols, ?r, ?c, "Piece overflow!");
-                        }
-                        if !term {
-                            return;
-                        }
-                    } else {
-                        break (r, c);
+                Ok(PieceInput::Dims(r, c)) => {
+                    if r > 0 && c > 0 {
+                        break Some((r, c));
+                    }
+                    error!(?r, ?c, "Invalid input");
+                    if !term {
+                        return None;
+                    }
+                },
+                Ok(PieceInput::Name(name)) => {
+                    if let Some(entry) = catalogue.get(&name) {
+                        debug!(?name, layout = ?entry.layout);
+                        pieces.push(entry.layout.clone());
+                        break None;
+                    }
+                    let names: Vec<&str> = catalogue.entries.iter().map(|entry| entry.name.as_str()).collect();
+                    error!(?name, known = names.join(" "), "Unknown piece");
+                    if !term {
+                        return None;
                     }
                 },
                 Err(e) => {
                     error!(error = %e, "Invalid input");
                     if !term {
-                        return;
+                        return None;
                     }
                 },
             }
         };
-        piece_avail.push(Available::new(rows, cols, r, c));
+        let Some((r, c)) = dims else {
+            continue;
+        };
         let mut piece: Array2<bool> = Array::from_elem((r, c), false);
         if term {println!("Layout:")}
         {
             let mut iterator = stdin().lock().lines();
             for j in 0..r {
-                let row_str = iterator.next().unwrap().unwrap();
+                let Some(Ok(row_str)) = iterator.next() else {
+                    error!("Unexpected end of input");
+                    return None;
+                };
                 for (k, ch) in row_str.chars().enumerate() {
                     if k == c {
                         break;
@@ -370,26 +610,579 @@ Row string input with insufficient length will leave the remaining cells empty."
                 }
             }
         }
-        pieces.push(piece);
+        // Only the cells have to fit on the board, not the box they were typed in
+        let trimmed = piece::trim(&piece);
+        let (trimmed_r, trimmed_c) = trimmed.dim();
+        if rows < trimmed_r || cols < trimmed_c {
+            error!(?rows, ?cols, r = ?trimmed_r, c = ?trimmed_c, "Piece overflow!");
+            if !term {
+                return None;
+            }
+            continue;
+        }
+        pieces.push(trimmed);
     }
-    let result = solve(&mut place_order, &mut piece_order, &mut lines_cleared, &pieces, &piece_avail, &mut state, args.sjt, args.wooden.then_some(chunk));
-    if let Some(clears) = result {
-        info!(?clears, "Solution found");
+    Some(BlockBlastPuzzle { board, pieces, rules: Rules { chunk, void, regions, ..Rules::default() }, combo: Combo::default(), fixed: Vec::new() })
+}
+/// Puzzle of a document, with the chunk of `--chunk` for `--wooden` when it has none.
+fn json_puzzle(args: &Args, json: &str) -> Result<BlockBlastPuzzle, String> {
+    let mut puzzle = match PuzzleDoc::parse(json).and_then(|doc| doc.to_puzzle()) {
+        Ok(puzzle) => puzzle,
+        Err(e) => return Err(e),
+    };
+    if args.wooden && puzzle.rules.chunk.is_none() {
+        let Some(chunk) = args.chunk else {
+            return Err("--wooden needs a chunk in the input or --chunk".to_owned());
+        };
+        puzzle.rules.chunk = Some(chunk);
+    }
+    Ok(puzzle)
+}
+/// Reads a puzzle document from stdin.
+fn read_json(args: &Args) -> Option<BlockBlastPuzzle> {
+    let mut json = String::new();
+    if let Err(e) = stdin().read_to_string(&mut json) {
+        error!(error = %e, "Cannot read the input");
+        return None;
+    }
+    json_puzzle(args, &json).inspect_err(|e| error!(error = %e, "Invalid input")).ok()
+}
+/// Reads the board and pieces from a screenshot.
+fn read_screenshot(args: &Args, path: &Path) -> Option<BlockBlastPuzzle> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) => {
+            error!(error = %e, ?path, "Cannot open the screenshot");
+            return None;
+        },
+    };
+    let defaults = Calibration::default();
+    let (rows, cols) = match args.cells {
+        Some(Chunk(r, c)) => (r, c),
+        None if args.rules == Preset::Woodoku => (board_size(args), board_size(args)),
+        None => (defaults.rows, defaults.cols),
+    };
+    let calibration = Calibration {
+        rows,
+        cols,
+        grid: args.grid,
+        tray: args.tray,
+        threshold: args.threshold.unwrap_or(defaults.threshold),
+        tolerance: args.tolerance.unwrap_or(defaults.tolerance),
+        piece_scale: args.piece_scale.unwrap_or(defaults.piece_scale),
+        ..defaults
+    };
+    let screenshot = match screenshot::import(BufReader::new(file), &calibration) {
+        Ok(screenshot) => screenshot,
+        Err(e) => {
+            error!(error = %e, ?path, "Cannot read the screenshot");
+            return None;
+        },
+    };
+    info!(grid = ?screenshot.grid, pieces = screenshot.pieces.len(), "Screenshot read");
+    let chunk = if args.wooden {
+        let Some(chunk) = args.chunk else {
+            error!("--wooden needs --chunk with --image");
+            return None;
+        };
+        Some(chunk)
     } else {
-        warn!("Unsolvable!");
-    }
-    print_grid(&state.slice(s![0_usize, .., ..]));
-    if result.is_some() {
-        for (i, grid) in state.axis_iter(Axis(0)).skip(1).enumerate() {
-            let avail_c = piece_avail[i].0;
-            let pos = place_order[i];
-            println!("Piece {}: {} {}", piece_order[i]+1, pos / avail_c, pos % avail_c);
-            print_grid(&grid);
-            match lines_cleared[i] {
-                0 => {},
-                1 => println!("(1 clear)"),
-                c => println!("({c} clears)"),
-            }
+        None
+    };
+    Some(BlockBlastPuzzle { board: screenshot.board, pieces: screenshot.pieces, rules: Rules { chunk, ..Rules::default() }, combo: Combo::default(), fixed: Vec::new() })
+}
+/// Characters of `rows` lines of `cols` cells, shorter lines leaving the rest empty (`.`).
+fn parse_grid<'input>(lines: &mut impl Iterator<Item = (usize, &'input str)>, rows: usize, cols: usize) -> Result<Array2<char>, String> {
+    let mut grid: Array2<char> = Array::from_elem((rows, cols), '.');
+    for i in 0..rows {
+        let Some((_, row_str)) = lines.next() else {
+            return Err("unexpected end of input".to_owned());
+        };
+        for (j, ch) in row_str.chars().take(cols).enumerate() {
+            grid[[i, j]] = ch;
+        }
+    }
+    Ok(grid)
+}
+/// Numbers of a line separated by whitespace.
+fn numbers(line: &str) -> Result<Vec<usize>, String> {
+    line.split_whitespace().map(|n| n.parse::<usize>().map_err(|e| e.to_string())).collect()
+}
+/// Reads one puzzle in the text format of [`read_puzzle`], answers only.
+fn parse_text<'input>(args: &Args, catalogue: &Catalogue, lines: &mut impl Iterator<Item = (usize, &'input str)>) -> Result<BlockBlastPuzzle, String> {
+    let Some((at, header)) = lines.next() else {
+        return Err("unexpected end of input".to_owned());
+    };
+    let (rows, cols, n_pieces, n_regions) = match numbers(header).as_deref() {
+        Ok(&[rows, cols, n_pieces]) if rows > 0 && cols > 0 && n_pieces > 0 => (rows, cols, n_pieces, 0),
+        Ok(&[rows, cols, n_pieces, n_regions]) if rows > 0 && cols > 0 && n_pieces > 0 => (rows, cols, n_pieces, n_regions),
+        _ => return Err(format!("line {}: expected the grid rows, columns, number of pieces and optionally of regions", at+1)),
+    };
+    let mut chunk = args.chunk.filter(|_| args.wooden);
+    if args.wooden && chunk.is_none() {
+        let Some((chunk_at, line)) = lines.next() else {
+            return Err("unexpected end of input".to_owned());
+        };
+        match numbers(line).as_deref() {
+            Ok(&[r, c]) if r > 0 && c > 0 && rows % r == 0 && cols % c == 0 => chunk = Some(Chunk(r, c)),
+            _ => return Err(format!("line {}: expected chunk dimensions distributing over the grid evenly", chunk_at+1)),
+        }
+    }
+    let (board, void) = match parse_grid(lines, rows, cols) {
+        Ok(cells) => board_cells(&cells),
+        Err(e) => return Err(e),
+    };
+    let mut regions: Vec<Region> = Vec::with_capacity(n_regions);
+    for _ in 0..n_regions {
+        let Some((region_at, line)) = lines.next() else {
+            return Err("unexpected end of input".to_owned());
+        };
+        match line.parse::<Region>() {
+            Ok(region) => regions.push(region),
+            Err(e) => return Err(format!("line {}: {e}", region_at+1)),
+        }
+    }
+    let mut pieces: Vec<Array2<bool>> = Vec::with_capacity(n_pieces);
+    for _ in 0..n_pieces {
+        let Some((piece_at, line)) = lines.next() else {
+            return Err("unexpected end of input".to_owned());
+        };
+        let layout = match numbers(line).as_deref() {
+            Ok(&[r, c]) if r > 0 && c > 0 => match parse_grid(lines, r, c) {
+                // Only the cells have to fit on the board, not the box they were typed in
+                Ok(cells) => {
+                    let trimmed = piece::trim(&cells.mapv(|ch| ch != '.'));
+                    let (trimmed_r, trimmed_c) = trimmed.dim();
+                    if trimmed_r <= rows && trimmed_c <= cols {Ok(trimmed)} else {Err(format!("line {}: piece cells do not fit within the grid", piece_at+1))}
+                },
+                Err(e) => Err(e),
+            },
+            Ok(_) => Err(format!("line {}: expected positive piece dimensions", piece_at+1)),
+            Err(_) => catalogue.get(line.trim()).map(|entry| entry.layout.clone())
+                .ok_or_else(|| format!("line {}: unknown piece {:?}", piece_at+1, line.trim())),
+        };
+        match layout {
+            Ok(piece) => pieces.push(piece),
+            Err(e) => return Err(e),
         }
     }
+    Ok(BlockBlastPuzzle { board, pieces, rules: Rules { chunk, void, regions, ..Rules::default() }, combo: Combo::default(), fixed: Vec::new() })
+}
+/// Puzzles of a batch: one document per line in JSON, or the answers to the prompts of
+/// [`read_puzzle`] one puzzle after the other in text, blank lines apart. Text cannot be read past
+/// a malformed puzzle, so it ends the batch.
+fn parse_batch(args: &Args, input: &str) -> Vec<Result<BlockBlastPuzzle, String>> {
+    let mut lines = input.lines().enumerate().peekable();
+    if args.input_format == Format::Json {
+        return lines.filter(|&(_, line)| !line.trim().is_empty())
+            .map(|(at, line)| json_puzzle(args, line).map_err(|e| format!("line {}: {e}", at+1))).collect();
+    }
+    let catalogue = Catalogue::standard();
+    let mut puzzles = Vec::new();
+    loop {
+        while lines.next_if(|&(_, line)| line.trim().is_empty()).is_some() {}
+        if lines.peek().is_none() {
+            return puzzles;
+        }
+        let puzzle = parse_text(args, &catalogue, &mut lines);
+        let malformed = puzzle.is_err();
+        puzzles.push(puzzle);
+        if malformed {
+            return puzzles;
+        }
+    }
+}
+fn micros(outcome: &Outcome) -> usize {
+    usize::try_from(outcome.elapsed.as_micros()).unwrap_or(usize::MAX)
+}
+/// Prints the result of the `index`-th puzzle of a batch on one line.
+fn print_record(index: usize, record: Result<&Outcome, &String>, args: &Args) {
+    let outcome = match record {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            println!("Puzzle {}: invalid input, {e}", index+1);
+            return;
+        },
+    };
+    let time = micros(outcome);
+    let solutions = match outcome.result.as_deref() {
+        Ok(solutions) => solutions,
+        Err(e) => {
+            println!("Puzzle {}: {e}, {time} us", index+1);
+            return;
+        },
+    };
+    let Some(best) = solutions.first() else {
+        return;
+    };
+    let moves: Vec<String> = best.steps.iter().map(|step| format!("{}@{},{}", step.piece+1, step.row, step.col)).collect();
+    let score = match args.scoring {
+        ScoringArg::Lines => String::new(),
+        ScoringArg::Points => format!(", score {}", best.score),
+    };
+    let found = if args.top_k.is_some() {format!(", {} solutions", solutions.len())} else {String::new()};
+    let proven = if best.optimal {""} else {", not proven optimal"};
+    println!("Puzzle {}: {} clears{score}{found}{proven}, {} nodes, {time} us, moves {}", index+1, best.clears, best.stats.nodes, moves.join(" "));
+}
+fn run_analysis(args: &Args, analyze_args: &AnalyzeArgs, puzzle: &BlockBlastPuzzle) {
+    let analysis = Analysis {
+        deal: analyze_args.deal,
+        samples: (analyze_args.samples > 0).then_some(analyze_args.samples),
+        seed: analyze_args.seed,
+        jobs: analyze_args.jobs,
+        ..Analysis::default()
+    };
+    let options = solve_options(args);
+    cancel_on_interrupt(&options.cancel);
+    let report = match risk::analyze(puzzle, &analysis, &options) {
+        Ok(report) => report,
+        Err(e) => {
+            error!(error = %e, "Analysis failed");
+            return;
+        },
+    };
+    if args.output_format == Format::Json {
+        match serde_json::to_string_pretty(&AnalysisDoc::new(&report, &analysis.catalogue)) {
+            Ok(json) => println!("{json}"),
+            Err(e) => error!(error = %e, "Cannot write the analysis"),
+        }
+        return;
+    }
+    // Permille to print one decimal without floats
+    let placeable = report.placeable * 1000 / report.deals.max(1);
+    let kind = if analysis.samples.is_some() {"sampled deals"} else {"deals, weighted by probability"};
+    println!("Placeable: {}.{}% of {kind}", placeable / 10, placeable % 10);
+    if report.unknown > 0 {
+        let unknown = report.unknown * 1000 / report.deals;
+        warn!("Stopped early, {}.{}% of the {kind} are unchecked and counted as not placeable", unknown / 10, unknown % 10);
+    }
+    let unfit: Vec<&str> = report.unfit.iter().map(|&i| analysis.catalogue.entries[i].name.as_str()).collect();
+    println!("Pieces that fit nowhere: {}", if unfit.is_empty() {"none".to_owned()} else {unfit.join(" ")});
+    println!("Cells no piece can fill: {}", report.dead.len());
+    if report.dead.is_empty() {
+        return;
+    }
+    let void = puzzle.rules.void.as_ref();
+    for (i, row) in puzzle.board.rows().into_iter().enumerate() {
+        let line: String = row.iter().enumerate().map(|(j, &filled)| {
+            if void.is_some_and(|mask| mask[[i, j]]) {'X'} else if filled {'#'} else if report.dead.contains(&(i, j)) {'!'} else {'.'}
+        }).collect();
+        println!("{line}");
+    }
+}
+/// `puzzle` in the text input format, pieces of the catalogue by name.
+fn puzzle_text(puzzle: &BlockBlastPuzzle, catalogue: &Catalogue) -> String {
+    let (rows, cols) = puzzle.board.dim();
+    let grid = |layout: &Array2<bool>| layout.rows().into_iter().map(|row| row.iter().map(|&k| if k {'#'} else {'.'}).collect::<String>()).collect::<Vec<String>>();
+    let mut lines = vec![format!("{rows} {cols} {}", puzzle.pieces.len())];
+    lines.extend(grid(&puzzle.board));
+    for layout in &puzzle.pieces {
+        if let Some(entry) = catalogue.entries.iter().find(|entry| entry.layout == *layout) {
+            lines.push(entry.name.clone());
+        } else {
+            let (r, c) = layout.dim();
+            lines.push(format!("{r} {c}"));
+            lines.extend(grid(layout));
+        }
+    }
+    lines.join("\n")
+}
+fn run_generation(args: &Args, gen_args: &GenerateArgs) {
+    if args.wooden && args.chunk.is_none() {
+        error!("--wooden needs --chunk to generate");
+        return;
+    }
+    let mut rules = Rules { chunk: args.chunk.filter(|_| args.wooden), ..Rules::default() };
+    apply_rules(args, &mut rules);
+    let generation = Generation {
+        rows: gen_args.rows.unwrap_or_else(|| board_size(args)),
+        cols: gen_args.cols.unwrap_or_else(|| board_size(args)),
+        rules,
+        deal: gen_args.deal,
+        density: gen_args.density,
+        constraint: gen_args.constraint,
+        count: gen_args.count,
+        tries: gen_args.tries,
+        seed: gen_args.seed,
+        ..Generation::default()
+    };
+    let options = solve_options(args);
+    cancel_on_interrupt(&options.cancel);
+    let puzzles = match generate(&generation, &options) {
+        Ok(puzzles) => puzzles,
+        Err(e) => {
+            error!(error = %e, "Generation failed");
+            return;
+        },
+    };
+    if puzzles.len() < gen_args.count {
+        let why = if options.cancel.is_cancelled() {"Stopped early, printing the puzzles found so far"} else {"Ran out of tries"};
+        warn!(found = puzzles.len(), wanted = gen_args.count, "{why}");
+    }
+    for puzzle in &puzzles {
+        println!("{}", puzzle_text(puzzle, &generation.catalogue));
+    }
+}
+fn run_batch(args: &Args, batch_args: &BatchArgs) {
+    let read = match batch_args.input.as_deref() {
+        Some(path) if path != Path::new("-") => fs::read_to_string(path),
+        _ => {
+            let mut stream = String::new();
+            stdin().read_to_string(&mut stream).map(|_| stream)
+        },
+    };
+    let input = match read {
+        Ok(input) => input,
+        Err(e) => {
+            error!(error = %e, path = ?batch_args.input, "Cannot read the input");
+            return;
+        },
+    };
+    let mut parsed = parse_batch(args, &input);
+    for puzzle in parsed.iter_mut().flatten() {
+        apply_rules(args, &mut puzzle.rules);
+        check_pieces(puzzle);
+    }
+    let puzzles: Vec<BlockBlastPuzzle> = parsed.iter().flatten().cloned().collect();
+    let options = solve_options(args);
+    cancel_on_interrupt(&options.cancel);
+    if options.lookahead.is_some() && args.top_k.is_some() {
+        warn!("--lookahead is ignored with --top-k");
+    }
+    let start = Instant::now();
+    let mut outcomes = batch::solve(&puzzles, &options, args.top_k, batch_args.jobs).into_iter();
+    let wall_micros = usize::try_from(start.elapsed().as_micros()).unwrap_or(usize::MAX);
+    let json = args.output_format == Format::Json;
+    let (mut unsolvable, mut invalid, mut unproven, mut times, mut nodes) = (Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    let puzzles_count = parsed.len();
+    for (index, input) in parsed.into_iter().enumerate() {
+        let void = input.as_ref().ok().and_then(|puzzle| puzzle.rules.void.clone());
+        let record = input.and_then(|_| outcomes.next().ok_or_else(|| "not solved".to_owned()));
+        match record.as_ref().map(|outcome| (micros(outcome), outcome.result.as_deref())) {
+            Ok((time, Ok(solutions))) => {
+                times.push(time);
+                nodes.push(solutions.first().map_or(0, |best| best.stats.nodes));
+                if solutions.iter().any(|solution| !solution.optimal) {
+                    unproven.push(index);
+                }
+            },
+            Ok((time, Err(&SolveError::Unsolvable))) => {
+                times.push(time);
+                unsolvable.push(index);
+            },
+            Ok((time, Err(&SolveError::Interrupted))) => {
+                times.push(time);
+                unproven.push(index);
+            },
+            Ok((_, Err(_))) | Err(_) => invalid.push(index),
+        }
+        if !json {
+            print_record(index, record.as_ref(), args);
+            continue;
+        }
+        let doc = match record {
+            Ok(outcome) => RecordDoc {
+                index,
+                micros: micros(&outcome),
+                nodes: outcome.result.as_deref().ok().and_then(|solutions| solutions.first()).map_or(0, |best| best.stats.nodes),
+                solution: SolutionDoc::new(outcome.result.as_deref(), args.top_k.is_some(), void.as_ref()),
+            },
+            Err(e) => RecordDoc { index, micros: 0, nodes: 0, solution: SolutionDoc { error: Some(e), ..SolutionDoc::new(Ok(&[]), false, None) } },
+        };
+        match serde_json::to_string(&doc) {
+            Ok(line) => println!("{line}"),
+            Err(e) => error!(error = %e, "Cannot write the record"),
+        }
+    }
+    let summary = SummaryDoc {
+        version: SCHEMA_VERSION,
+        puzzles: puzzles_count,
+        solved: nodes.len(),
+        unsolvable,
+        invalid,
+        unproven,
+        wall_micros,
+        micros: Spread::of(times.clone()),
+        nodes: Spread::of(nodes),
+    };
+    if json {
+        match serde_json::to_string(&summary) {
+            Ok(line) => println!("{line}"),
+            Err(e) => error!(error = %e, "Cannot write the summary"),
+        }
+        return;
+    }
+    let listed = |indices: &[usize]| indices.iter().map(|index| (index+1).to_string()).collect::<Vec<String>>().join(" ");
+    println!("Puzzles: {}, solved {}, unsolvable {}, invalid {}", summary.puzzles, summary.solved, summary.unsolvable.len(), summary.invalid.len());
+    if !summary.unsolvable.is_empty() {
+        println!("Unsolvable: {}", listed(&summary.unsolvable));
+    }
+    if !summary.invalid.is_empty() {
+        println!("Invalid: {}", listed(&summary.invalid));
+    }
+    if !summary.unproven.is_empty() {
+        println!("Not proven optimal: {}", listed(&summary.unproven));
+    }
+    println!("Wall time: {wall_micros} us");
+    print_spread("Time (us)", &summary.micros, times.len());
+    print_spread("Nodes", &summary.nodes, summary.solved);
+}
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    // Keep the JSON and the generated puzzles on stdout parseable
+    let writer = if args.output_format == Format::Json || matches!(args.command, Some(Command::Generate(_))) {BoxMakeWriter::new(io::stderr)} else {BoxMakeWriter::new(io::stdout)};
+    tracing_subscriber::fmt()
+        .with_writer(writer)
+        .with_env_filter(filter)
+        .with_target(false)
+        .with_line_number(true)
+        .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE)
+        .with_timer(Uptime::default())
+        .init();
+    let _main_span = info_span!("main").entered();
+    if args.command.is_some() && !args.fixed.is_empty() {
+        warn!("--fixed only applies to a single puzzle and is ignored");
+    }
+    let analyze = match args.command.clone() {
+        Some(Command::Simulate(sim)) => {
+            run_simulation(&args, &sim);
+            return ExitCode::SUCCESS;
+        },
+        Some(Command::Batch(batch_args)) => {
+            run_batch(&args, &batch_args);
+            return ExitCode::SUCCESS;
+        },
+        Some(Command::Generate(gen_args)) => {
+            run_generation(&args, &gen_args);
+            return ExitCode::SUCCESS;
+        },
+        Some(Command::Analyze(analyze_args)) => Some(analyze_args),
+        None => None,
+    };
+    info!(terminal = ?stdin().is_terminal(), ?args);
+    let mut puzzle = if let Some(path) = args.image.as_ref() {
+        let Some(puzzle) = read_screenshot(&args, path) else {
+            return ExitCode::FAILURE;
+        };
+        puzzle
+    } else if args.input_format == Format::Json {
+        let Some(puzzle) = read_json(&args) else {
+            return ExitCode::FAILURE;
+        };
+        puzzle
+    } else {
+        let Some(puzzle) = read_puzzle(&args) else {
+            return ExitCode::FAILURE;
+        };
+        puzzle
+    };
+    apply_rules(&args, &mut puzzle.rules);
+    if let Some(analyze_args) = analyze {
+        run_analysis(&args, &analyze_args, &puzzle);
+        return ExitCode::SUCCESS;
+    }
+    puzzle.fixed.extend_from_slice(&args.fixed);
+    check_pieces(&puzzle);
+    let options = solve_options(&args);
+    cancel_on_interrupt(&options.cancel);
+    if options.lookahead.is_some() && args.top_k.is_some() {
+        warn!("--lookahead is ignored with --top-k");
+    }
+    let result = args.top_k.map_or_else(|| {
+        solve_with(&puzzle, &options).map(|solution| {
+            info!(clears = ?solution.clears, score = ?solution.score, quality = ?solution.quality,
+                  nodes = ?solution.stats.nodes, pruned = ?solution.stats.pruned, "Solution found");
+            let stats = solution.stats;
+            info!(lookups = ?stats.table_lookups, hits = ?stats.table_hits,
+                  permille = stats.table_hits * 1000 / stats.table_lookups.max(1), "Transposition table");
+            vec![solution]
+        })
+    }, |k| {
+        solve_top_k(&puzzle, &options, k).inspect(|solutions| info!(found = solutions.len(), "Solutions found"))
+    });
+    let code = match result.as_deref() {
+        Ok(solutions) if solutions.iter().any(|solution| !solution.optimal) => {
+            warn!("Stopped early, the solution is not proven optimal");
+            ExitCode::SUCCESS
+        },
+        Ok(_) => ExitCode::SUCCESS,
+        Err(&SolveError::Unsolvable) => {
+            warn!("Unsolvable!");
+            ExitCode::SUCCESS
+        },
+        Err(&SolveError::Interrupted) => {
+            warn!("Stopped before any solution was found");
+            ExitCode::SUCCESS
+        },
+        Err(e) => {
+            error!(error = %e, "Invalid input");
+            ExitCode::FAILURE
+        },
+    };
+    if let Some(path) = args.export.as_deref() && let Some(best) = result.as_deref().ok().and_then(<[Solution]>::first) {
+        export(path, &puzzle, best);
+    }
+    if args.output_format == Format::Json {
+        let doc = SolutionDoc::new(result.as_deref(), args.top_k.is_some(), puzzle.rules.void.as_ref());
+        match serde_json::to_string_pretty(&doc) {
+            Ok(json) => println!("{json}"),
+            Err(e) => error!(error = %e, "Cannot write the solution"),
+        }
+        return code;
+    }
+    if code != ExitCode::SUCCESS {
+        return code;
+    }
+    let void = puzzle.rules.void.as_ref();
+    print_grid(&puzzle.board.view(), void);
+    let color = match args.color {
+        ColorArg::Auto => io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()),
+        ColorArg::Always => true,
+        ColorArg::Never => false,
+    };
+    let solutions = result.unwrap_or_default();
+    if args.top_k.is_none() {
+        if let Some(solution) = solutions.first() {
+            print_solution(&puzzle, solution, args.scoring, color);
+        }
+        return code;
+    }
+    for (rank, solution) in solutions.iter().enumerate() {
+        println!();
+        match args.scoring {
+            ScoringArg::Lines => println!("Solution {}: {} clears", rank+1, solution.clears),
+            ScoringArg::Points => println!("Solution {}: {} clears, score {}", rank+1, solution.clears, solution.score),
+        }
+        print_solution(&puzzle, solution, args.scoring, color);
+    }
+    code
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    /// Reads fixture `name` from its screenshot and from its text, which must give the same board
+    /// and pieces.
+    fn same_puzzle(name: &str, calibration: &Calibration) {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/screenshots");
+        let image = File::open(dir.join(format!("{name}.png"))).unwrap();
+        let screenshot = screenshot::import(BufReader::new(image), calibration).unwrap();
+        let text = fs::read_to_string(dir.join(format!("{name}.txt"))).unwrap();
+        let args = Args::parse_from(["block_blast_solver"]);
+        let puzzle = parse_text(&args, &Catalogue::standard(), &mut text.lines().enumerate()).unwrap();
+        assert_eq!(screenshot.board, puzzle.board, "board of {name}");
+        assert_eq!(screenshot.pieces, puzzle.pieces, "pieces of {name}");
+    }
+    #[test]
+    fn opening() {
+        same_puzzle("opening", &Calibration::default());
+    }
+    #[test]
+    fn midgame() {
+        same_puzzle("midgame", &Calibration::default());
+    }
+    #[test]
+    fn large() {
+        same_puzzle("large", &Calibration { rows: 10, cols: 10, piece_scale: 60, ..Calibration::default() });
+    }
 }
@@ -0,0 +1,267 @@
+use crate::{Available, Chunk, Resolution, Rules};
+use ndarray::prelude::*;
+use core::hash::Hash;
+/// Board representation the search places pieces on.
+pub trait Board: Clone + Send {
+    type State: Clone + Eq + Hash + Send + Sync;
+    fn load(&self, grid: &Array2<bool>) -> Self::State;
+    fn store(&self, state: &Self::State) -> Array2<bool>;
+    /// Places `piece` at its `pos`-th anchor (row-major over [`Available`]) on `src`, writing the
+    /// cleared result into `dst`. Returns the clears, or `None` if the piece overlaps a filled cell.
+    fn place(&mut self, src: &Self::State, dst: &mut Self::State, piece: usize, pos: usize) -> Option<usize>;
+    /// Upper bound on the clears `cells` more filled cells can still make on `state`.
+    fn clear_bound(&mut self, state: &Self::State, cells: usize) -> usize;
+    /// Bytes taken by `state`, including its heap allocation.
+    fn state_bytes(&self, state: &Self::State) -> usize;
+}
+/// Most lines out of a family of disjoint `len`-cell lines that `cells` more filled cells can
+/// complete, given how many cells each line is still missing. Every cell counts towards one line
+/// of the family only, and completing a line again after its clear takes `len` more cells.
+pub fn completable(missing: &mut [usize], len: usize, cells: usize) -> usize {
+    missing.sort_unstable();
+    let mut left = cells;
+    let mut count = 0_usize;
+    for &m in &*missing {
+        if m > left {
+            return count;
+        }
+        left -= m;
+        count += 1;
+    }
+    count + left / len
+}
+/// Whether the pieces of `order` can all be placed in that order, starting from `state`.
+pub fn fits_in_order(board: &mut ArrayBoard, state: &Array2<bool>, order: &[usize], piece_avail: &[Available]) -> bool {
+    let Some((&piece, rest)) = order.split_first() else {
+        return true;
+    };
+    let mut next = state.clone();
+    for pos in 0..piece_avail[piece].1 {
+        if board.place(state, &mut next, piece, pos).is_some() && fits_in_order(board, &next, rest, piece_avail) {
+            return true;
+        }
+    }
+    false
+}
+/// Whether each line has a cell that is not void, and the fewest such cells of a line.
+fn live<'line>(lines: impl Iterator<Item = ArrayView2<'line, bool>>) -> (Vec<bool>, usize) {
+    let counts: Vec<usize> = lines.map(|line| line.iter().filter(|&&dead| !dead).count()).collect();
+    let shortest = counts.iter().copied().filter(|&n| n > 0).min().unwrap_or(0);
+    (counts.into_iter().map(|n| n > 0).collect(), shortest)
+}
+/// Generic fallback for boards of any size, one `bool` per cell.
+///
+/// Void cells stay filled in every state, and lines made of void cells only are never complete.
+#[derive(Clone, Debug)]
+pub struct ArrayBoard {
+    pieces: Vec<Array2<bool>>,
+    piece_avail: Vec<Available>,
+    chunk: Option<Chunk>,
+    void: Option<Array2<bool>>,
+    resolution: Resolution,
+    // Lines with a cell that is not void and cleared by the rules, rows, columns, then chunks
+    row_live: Vec<bool>,
+    col_live: Vec<bool>,
+    chunk_live: Vec<bool>,
+    // Fewest cells that are not void in a row, a column, then a chunk
+    lens: [usize; 3],
+    // Index in the rules, cells and count of cells that are not void of every region with any
+    region_index: Vec<usize>,
+    regions: Vec<Vec<(usize, usize)>>,
+    region_lens: Vec<usize>,
+    row_filled: Vec<bool>,
+    col_filled: Vec<bool>,
+    region_filled: Vec<bool>,
+    missing: Vec<usize>,
+}
+impl ArrayBoard {
+    pub fn new(rows: usize, cols: usize, pieces: &[Array2<bool>], piece_avail: &[Available], rules: &Rules) -> Self {
+        let (chunk, void) = (rules.chunk.filter(|_| rules.clear_regions), rules.void.as_ref());
+        let mask = void.cloned().unwrap_or_else(|| Array::from_elem((rows, cols), false));
+        let kept = if rules.clear_regions {rules.regions.as_slice()} else {&[]};
+        let mut region_index = Vec::new();
+        let (mut regions, mut region_lens) = (Vec::new(), Vec::new());
+        for (index, region) in kept.iter().enumerate() {
+            let len = region.cells.iter().filter(|&&cell| !mask[cell]).count();
+            if len > 0 {
+                region_index.push(index);
+                regions.push(region.cells.clone());
+                region_lens.push(len);
+            }
+        }
+        let (mut row_live, mut row_len) = live(mask.axis_iter(Axis(0)).map(|row| row.insert_axis(Axis(0))));
+        let (mut col_live, mut col_len) = live(mask.axis_iter(Axis(1)).map(|col| col.insert_axis(Axis(0))));
+        if !rules.clear_rows {
+            row_live.fill(false);
+            row_len = 0;
+        }
+        if !rules.clear_cols {
+            col_live.fill(false);
+            col_len = 0;
+        }
+        let (chunk_live, chunk_len) = chunk.map_or((Vec::new(), 0), |Chunk(chunk_r, chunk_c)| live(mask.exact_chunks((chunk_r, chunk_c)).into_iter()));
+        Self {
+            pieces: pieces.to_vec(),
+            piece_avail: piece_avail.to_vec(),
+            chunk,
+            void: void.cloned(),
+            resolution: rules.resolution,
+            row_live,
+            col_live,
+            chunk_live,
+            lens: [row_len, col_len, chunk_len],
+            region_filled: vec![false; regions.len()],
+            region_index,
+            regions,
+            region_lens,
+            row_filled: vec![false; rows],
+            col_filled: vec![false; cols],
+            missing: Vec::with_capacity(rows.max(cols)),
+        }
+    }
+    /// Indices in [`Rules::regions`] of the regions cleared by the last placement.
+    pub fn cleared_regions(&self) -> Vec<usize> {
+        self.region_filled.iter().zip(&self.region_index).filter(|&(&filled, _)| filled).map(|(_, &index)| index).collect()
+    }
+    fn mark_rows(&mut self, state: &Array2<bool>) {
+        for (i, row) in state.axis_iter(Axis(0)).enumerate() {
+            self.row_filled[i] = self.row_live[i] && row.iter().all(|&x| x);
+        }
+    }
+    fn mark_cols(&mut self, state: &Array2<bool>) {
+        for (i, col) in state.axis_iter(Axis(1)).enumerate() {
+            self.col_filled[i] = self.col_live[i] && col.iter().all(|&x| x);
+        }
+    }
+    fn mark_regions(&mut self, state: &Array2<bool>) {
+        for (filled, cells) in self.region_filled.iter_mut().zip(&self.regions) {
+            *filled = cells.iter().all(|&cell| state[cell]);
+        }
+    }
+    fn clear_rows(&self, state: &mut Array2<bool>) -> usize {
+        let mut clear = 0_usize;
+        for (i, mut row) in state.axis_iter_mut(Axis(0)).enumerate() {
+            if self.row_filled[i] {
+                clear += 1;
+                row.fill(false);
+            }
+        }
+        clear
+    }
+    fn clear_cols(&self, state: &mut Array2<bool>) -> usize {
+        let mut clear = 0_usize;
+        for (i, mut col) in state.axis_iter_mut(Axis(1)).enumerate() {
+            if self.col_filled[i] {
+                clear += 1;
+                col.fill(false);
+            }
+        }
+        clear
+    }
+    /// Chunks are disjoint, so they are cleared as soon as they are found.
+    fn clear_chunks(&self, state: &mut Array2<bool>) -> usize {
+        let mut clear = 0_usize;
+        if let Some(Chunk(chunk_r, chunk_c)) = self.chunk {
+            for (mut chunk, &chunk_live) in state.exact_chunks_mut((chunk_r, chunk_c)).into_iter().zip(&self.chunk_live) {
+                if chunk_live && chunk.iter().all(|&x| x) {
+                    clear += 1;
+                    chunk.fill(false);
+                }
+            }
+        }
+        clear
+    }
+    fn clear_regions(&self, state: &mut Array2<bool>) -> usize {
+        let mut clear = 0_usize;
+        for (&filled, cells) in self.region_filled.iter().zip(&self.regions) {
+            if filled {
+                clear += 1;
+                for &cell in cells {
+                    state[cell] = false;
+                }
+            }
+        }
+        clear
+    }
+}
+impl Board for ArrayBoard {
+    type State = Array2<bool>;
+    fn state_bytes(&self, state: &Self::State) -> usize {
+        size_of::<Self::State>() + state.len()
+    }
+    fn load(&self, grid: &Array2<bool>) -> Self::State {
+        let mut state = grid.clone();
+        if let Some(void) = self.void.as_ref() {
+            state.zip_mut_with(void, |cell, &dead| *cell |= dead);
+        }
+        state
+    }
+    fn store(&self, state: &Self::State) -> Array2<bool> {
+        state.clone()
+    }
+    fn place(&mut self, src: &Self::State, dst: &mut Self::State, piece: usize, pos: usize) -> Option<usize> {
+        let avail_c = self.piece_avail[piece].0;
+        let layout = &self.pieces[piece];
+        let (pos_r, pos_c) = (pos / avail_c, pos % avail_c);
+        for ((i, j), k) in layout.indexed_iter() {
+            if *k && src[[pos_r+i, pos_c+j]] {
+                return None;
+            }
+        }
+        let mut clear = 0_usize;
+        if let Some(d_raw) = dst.as_slice_mut() &&
+           let Some(s_raw) = src.as_slice() {
+            d_raw.copy_from_slice(s_raw);
+        } else {
+            dst.assign(src);
+        }
+        for ((i, j), k) in layout.indexed_iter() {
+            if *k {
+                dst[[pos_r+i, pos_c+j]] = true;
+            }
+        }
+        if self.resolution == Resolution::Cascading {
+            self.mark_rows(dst);
+            clear += self.clear_rows(dst);
+            self.mark_cols(dst);
+            clear += self.clear_cols(dst);
+            clear += self.clear_chunks(dst);
+            self.mark_regions(dst);
+            clear += self.clear_regions(dst);
+        } else {
+            self.mark_rows(dst);
+            self.mark_cols(dst);
+            self.mark_regions(dst);
+            clear += self.clear_chunks(dst) + self.clear_rows(dst) + self.clear_cols(dst) + self.clear_regions(dst);
+        }
+        if let Some(void) = self.void.as_ref() {
+            dst.zip_mut_with(void, |cell, &dead| *cell |= dead);
+        }
+        Some(clear)
+    }
+    fn clear_bound(&mut self, state: &Self::State, cells: usize) -> usize {
+        let [row_len, col_len, chunk_len] = self.lens;
+        let mut bound = 0_usize;
+        for (axis, live_lines, len) in [(Axis(0), &self.row_live, row_len), (Axis(1), &self.col_live, col_len)] {
+            if len == 0 {
+                continue;
+            }
+            self.missing.clear();
+            self.missing.extend(state.axis_iter(axis).zip(live_lines).filter(|&(_, &alive)| alive)
+                .map(|(line, _)| line.iter().filter(|&&x| !x).count()));
+            bound += completable(&mut self.missing, len, cells);
+        }
+        if let Some(Chunk(chunk_r, chunk_c)) = self.chunk && chunk_len > 0 {
+            self.missing.clear();
+            self.missing.extend(state.exact_chunks((chunk_r, chunk_c)).into_iter().zip(&self.chunk_live).filter(|&(_, &alive)| alive)
+                .map(|(chunk, _)| chunk.iter().filter(|&&x| !x).count()));
+            bound += completable(&mut self.missing, chunk_len, cells);
+        }
+        // Regions may overlap, so each one is a family of its own
+        for (region, &len) in self.regions.iter().zip(&self.region_lens) {
+            let mut missing = [region.iter().filter(|&&cell| !state[cell]).count()];
+            bound += completable(&mut missing, len, cells);
+        }
+        bound
+    }
+}
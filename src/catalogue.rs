@@ -0,0 +1,100 @@
+use crate::rng::Rng;
+use ndarray::prelude::*;
+use core::iter::repeat_with;
+// Every piece the game deals with its name and relative frequency, rows separated by '/'. Lines
+// are named by length and direction, other shapes by letter and size, with `r1`..`r3` for the
+// shape turned clockwise that many quarter turns
+const STANDARD: [(&str, &str, u32); 37] = [
+    ("I1", "#", 2),
+    ("I2h", "##", 4), ("I2v", "#/#", 4),
+    ("I3h", "###", 4), ("I3v", "#/#/#", 4),
+    ("I4h", "####", 3), ("I4v", "#/#/#/#", 3),
+    ("I5h", "#####", 2), ("I5v", "#/#/#/#/#", 2),
+    ("sq2", "##/##", 4),
+    ("sq3", "###/###/###", 2),
+    ("R6h", "###/###", 3), ("R6v", "##/##/##", 3),
+    ("L3", "#./##", 3), ("L3r3", ".#/##", 3), ("L3r1", "##/#.", 3), ("L3r2", "##/.#", 3),
+    ("L4", "#./#./##", 2), ("L4r1", "###/#..", 2), ("L4r2", "##/.#/.#", 2), ("L4r3", "..#/###", 2),
+    ("J4", ".#/.#/##", 2), ("J4r1", "#../###", 2), ("J4r2", "##/#./#.", 2), ("J4r3", "###/..#", 2),
+    ("L5", "#../#../###", 2), ("L5r1", "###/#../#..", 2), ("L5r2", "###/..#/..#", 2), ("L5r3", "..#/..#/###", 2),
+    ("T", "###/.#.", 2), ("Tr2", ".#./###", 2), ("Tr3", "#./##/#.", 2), ("Tr1", ".#/##/.#", 2),
+    ("S", ".##/##.", 2), ("Sr1", "#./##/.#", 2),
+    ("Z", "##./.##", 2), ("Zr1", ".#/##/#.", 2),
+];
+fn parse(layout: &str) -> Array2<bool> {
+    let rows: Vec<&str> = layout.split('/').collect();
+    let cols = rows[0].len();
+    Array::from_shape_fn((rows.len(), cols), |(i, j)| rows[i].as_bytes()[j] == b'#')
+}
+/// A piece that can be dealt.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    /// Name the piece can be looked up by.
+    pub name: String,
+    pub layout: Array2<bool>,
+    /// Relative frequency of the piece in a deal.
+    pub weight: u32,
+}
+/// Distribution of the pieces dealt each round.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Catalogue {
+    pub entries: Vec<Entry>,
+}
+impl Catalogue {
+    /// Every standard piece of the game.
+    #[must_use]
+    pub fn standard() -> Self {
+        let entries = STANDARD.iter().map(|&(name, layout, weight)| {
+            Entry { name: name.to_owned(), layout: parse(layout), weight }
+        }).collect();
+        Self { entries }
+    }
+    /// Piece called `name`.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+    #[must_use]
+    pub fn total_weight(&self) -> u64 {
+        self.entries.iter().map(|entry| u64::from(entry.weight)).sum()
+    }
+    /// Deals of `k` pieces with their weights, as indices into the entries: `samples` random ones
+    /// drawn from `seed` weighing 1 each, or every distinct one weighted by its probability.
+    #[must_use]
+    pub fn deals(&self, k: usize, samples: Option<usize>, seed: u64) -> Vec<(Vec<usize>, u64)> {
+        let weights: Vec<u64> = self.entries.iter().map(|entry| u64::from(entry.weight)).collect();
+        let n = weights.len();
+        if n == 0 || k == 0 {
+            return Vec::new();
+        }
+        if let Some(count) = samples {
+            let mut rng = Rng::new(seed);
+            let mut draw = || (repeat_with(|| rng.weighted(&weights)).take(k).collect(), 1);
+            return repeat_with(&mut draw).take(count).collect();
+        }
+        // Every multiset of k pieces, weighted by its multinomial probability
+        let wide = |m: usize| u64::try_from(m).unwrap_or(u64::MAX);
+        let mut result = Vec::new();
+        let mut deal: Vec<usize> = vec![0; k];
+        let factorial = |m: usize| (1..=wide(m)).product::<u64>();
+        loop {
+            let mut weight = factorial(k);
+            let mut run = 1_usize;
+            for i in 0..k {
+                weight *= weights[deal[i]];
+                if i > 0 && deal[i] == deal[i-1] {
+                    run += 1;
+                    weight /= wide(run);
+                } else {
+                    run = 1;
+                }
+            }
+            result.push((deal.clone(), weight));
+            let Some(i) = (0..k).rev().find(|&i| deal[i] + 1 < n) else {
+                return result;
+            };
+            let next = deal[i] + 1;
+            deal[i..].fill(next);
+        }
+    }
+}
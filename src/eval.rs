@@ -0,0 +1,107 @@
+use crate::catalogue::Catalogue;
+use ndarray::prelude::*;
+use core::str::FromStr;
+/// Features of a board that make the next rounds easier or harder.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Evaluation {
+    /// Empty cells without an empty orthogonal neighbour.
+    pub holes: usize,
+    /// Orthogonally adjacent pairs of one filled and one empty cell.
+    pub roughness: usize,
+    /// Area of the largest empty rectangle.
+    pub rectangle: usize,
+    /// Standard pieces that still fit somewhere.
+    pub fits: usize,
+}
+/// Weights of the [`Evaluation`] features; holes and roughness count against the board.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Weights {
+    pub holes: i64,
+    pub roughness: i64,
+    pub rectangle: i64,
+    pub fits: i64,
+}
+impl Default for Weights {
+    fn default() -> Self {
+        Self { holes: 8, roughness: 1, rectangle: 1, fits: 2 }
+    }
+}
+/// Parses `holes,roughness,rectangle,fits`.
+impl FromStr for Weights {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parsed: Result<Vec<i64>, _> = s.split(',').map(|w| w.trim().parse::<i64>()).collect();
+        match parsed.as_deref() {
+            Ok(&[holes, roughness, rectangle, fits]) => Ok(Self { holes, roughness, rectangle, fits }),
+            Ok(_) => Err(format!("expected 4 comma-separated weights, got {s:?}")),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+fn signed(n: usize) -> i64 {
+    i64::try_from(n).unwrap_or(i64::MAX)
+}
+impl Weights {
+    /// Weighted sum of `eval`, higher is better.
+    #[must_use]
+    pub fn value(&self, eval: &Evaluation) -> i64 {
+        self.rectangle * signed(eval.rectangle) + self.fits * signed(eval.fits)
+            - self.holes * signed(eval.holes) - self.roughness * signed(eval.roughness)
+    }
+}
+/// Computes [`Evaluation`]s of final boards.
+#[derive(Clone, Debug)]
+pub struct Evaluator {
+    pieces: Vec<Array2<bool>>,
+}
+impl Default for Evaluator {
+    fn default() -> Self {
+        Self { pieces: Catalogue::standard().entries.into_iter().map(|entry| entry.layout).collect() }
+    }
+}
+impl Evaluator {
+    #[must_use]
+    pub fn evaluate(&self, grid: &Array2<bool>) -> Evaluation {
+        let (rows, cols) = grid.dim();
+        let empty = |i: usize, j: usize| !grid[[i, j]];
+        let mut eval = Evaluation::default();
+        for ((i, j), &filled) in grid.indexed_iter() {
+            if i + 1 < rows && filled != grid[[i+1, j]] {
+                eval.roughness += 1;
+            }
+            if j + 1 < cols && filled != grid[[i, j+1]] {
+                eval.roughness += 1;
+            }
+            let open = (i > 0 && empty(i-1, j)) || (i + 1 < rows && empty(i+1, j)) ||
+                       (j > 0 && empty(i, j-1)) || (j + 1 < cols && empty(i, j+1));
+            if !filled && !open {
+                eval.holes += 1;
+            }
+        }
+        // Largest rectangle in the histogram of empty cells above each row
+        let mut heights: Vec<usize> = vec![0; cols];
+        let mut stack: Vec<usize> = Vec::with_capacity(cols);
+        for row in grid.axis_iter(Axis(0)) {
+            for (h, &filled) in heights.iter_mut().zip(row) {
+                *h = if filled {0} else {*h + 1};
+            }
+            stack.clear();
+            for j in 0..=cols {
+                let h = heights.get(j).copied().unwrap_or(0);
+                while let Some(&top) = stack.last() && heights[top] >= h {
+                    stack.pop();
+                    let left = stack.last().map_or(0, |&k| k + 1);
+                    eval.rectangle = eval.rectangle.max(heights[top] * (j - left));
+                }
+                stack.push(j);
+            }
+        }
+        eval.fits = self.pieces.iter().filter(|piece| {
+            let (r, c) = piece.dim();
+            r <= rows && c <= cols && (0..=rows-r).any(|pos_r| (0..=cols-c).any(|pos_c| {
+                piece.indexed_iter().all(|((i, j), &k)| !k || empty(pos_r+i, pos_c+j))
+            }))
+        }).count();
+        eval
+    }
+}
@@ -0,0 +1,222 @@
+use crate::{Available, BlockBlastPuzzle, Rules, SolveError, SolveOptions, solve_with};
+use crate::board::{ArrayBoard, Board as _, fits_in_order};
+use crate::catalogue::Catalogue;
+use crate::permutation::Permutation;
+use crate::piece;
+use crate::rng::Rng;
+use crate::scoring::{Combo, LinesCleared};
+use tracing::*;
+use ndarray::prelude::*;
+use alloc::sync::Arc;
+use core::iter::repeat_with;
+use core::str::FromStr;
+/// Property a generated puzzle must have.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Constraint {
+    /// Every piece can be placed.
+    Solvable,
+    /// Some piece cannot be placed whatever the order.
+    Unsolvable,
+    /// Solvable, with this many clears at best.
+    Clears(usize),
+    /// Solvable in a single order of the pieces, identical pieces being interchangeable.
+    UniqueOrder,
+    /// Unsolvable, but emptying one filled cell of the board makes it solvable.
+    NearMiss,
+}
+/// Parses `solvable`, `unsolvable`, `clears=N`, `unique-order` or `near-miss`.
+impl FromStr for Constraint {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "solvable" => Ok(Self::Solvable),
+            "unsolvable" => Ok(Self::Unsolvable),
+            "unique-order" => Ok(Self::UniqueOrder),
+            "near-miss" => Ok(Self::NearMiss),
+            other => match other.strip_prefix("clears=").map(str::parse::<usize>) {
+                Some(Ok(clears)) => Ok(Self::Clears(clears)),
+                Some(Err(e)) => Err(e.to_string()),
+                None => Err(format!("expected solvable, unsolvable, clears=N, unique-order or near-miss, got {s:?}")),
+            },
+        }
+    }
+}
+/// Puzzles made by [`generate`].
+#[derive(Clone, Debug)]
+pub struct Generation {
+    pub rows: usize,
+    pub cols: usize,
+    pub rules: Rules,
+    /// Distribution of the dealt pieces.
+    pub catalogue: Catalogue,
+    /// Pieces per deal.
+    pub deal: usize,
+    /// Chance in percent of every board cell to start filled.
+    pub density: u64,
+    pub constraint: Constraint,
+    /// Puzzles wanted.
+    pub count: usize,
+    /// Random candidates tried before giving up on the puzzles still missing.
+    pub tries: usize,
+    pub seed: u64,
+}
+impl Default for Generation {
+    fn default() -> Self {
+        Self {
+            rows: 8,
+            cols: 8,
+            rules: Rules::default(),
+            catalogue: Catalogue::standard(),
+            deal: 3,
+            density: 40,
+            constraint: Constraint::Solvable,
+            count: 10,
+            tries: 10_000,
+            seed: 0,
+        }
+    }
+}
+/// Distinct orders of the pieces of `puzzle` in which all of them can be placed, identical pieces
+/// being interchangeable. The puzzle must be valid.
+fn working_orders(puzzle: &BlockBlastPuzzle) -> usize {
+    let (rows, cols) = puzzle.board.dim();
+    let pieces: Vec<Array2<bool>> = puzzle.pieces.iter().map(piece::trim).collect();
+    let piece_avail: Vec<Available> = pieces.iter().map(|layout| {
+        let (r, c) = layout.dim();
+        Available::new(rows, cols, r, c)
+    }).collect();
+    let shapes: Vec<usize> = pieces.iter().map(|layout| pieces.iter().position(|other| other == layout).unwrap_or(0)).collect();
+    let mut board = ArrayBoard::new(rows, cols, &pieces, &piece_avail, &puzzle.rules);
+    let state = board.load(&puzzle.board);
+    let mut seen: Vec<Vec<usize>> = Vec::new();
+    let mut permutation = Permutation::new(pieces.len(), false);
+    loop {
+        let order = permutation.perm();
+        let shape_order: Vec<usize> = order.iter().map(|&piece| shapes[piece]).collect();
+        if !seen.contains(&shape_order) {
+            if fits_in_order(&mut board, &state, order, &piece_avail) {
+                seen.push(shape_order);
+            } else {
+                debug!(?order, "Order does not fit");
+            }
+        }
+        if !permutation.permute() {
+            return seen.len();
+        }
+    }
+}
+/// Whether `puzzle` has `constraint`, solving it with `options`. A solution cut short by the
+/// limits of `options` may miss clears, so the puzzle is then taken not to have it.
+fn satisfies(puzzle: &BlockBlastPuzzle, constraint: Constraint, options: &SolveOptions) -> Result<bool, SolveError> {
+    let solved = match solve_with(puzzle, options) {
+        Ok(solution) if !solution.optimal => return Ok(false),
+        Ok(solution) => Some(solution),
+        // A piece larger than the board is one that cannot be placed
+        Err(SolveError::Unsolvable | SolveError::InvalidPiece { .. }) => None,
+        Err(e) => return Err(e),
+    };
+    Ok(match constraint {
+        Constraint::Solvable => solved.is_some(),
+        Constraint::Unsolvable => solved.is_none(),
+        Constraint::Clears(clears) => solved.is_some_and(|solution| solution.clears == clears),
+        Constraint::UniqueOrder => solved.is_some() && working_orders(puzzle) == 1,
+        Constraint::NearMiss => {
+            if solved.is_some() {
+                return Ok(false);
+            }
+            let void = puzzle.rules.void.as_ref();
+            for ((i, j), &filled) in puzzle.board.indexed_iter() {
+                if !filled || void.is_some_and(|mask| mask[[i, j]]) {
+                    continue;
+                }
+                let mut board = puzzle.board.clone();
+                board[[i, j]] = false;
+                let eased = BlockBlastPuzzle { board, ..puzzle.clone() };
+                match solve_with(&eased, options) {
+                    Ok(_) => return Ok(true),
+                    Err(SolveError::Unsolvable | SolveError::InvalidPiece { .. }) => {},
+                    Err(e) => return Err(e),
+                }
+            }
+            false
+        },
+    })
+}
+/// Draws random boards and deals from [`Generation::seed`] until [`Generation::count`] of them
+/// have the [`Generation::constraint`], or [`Generation::tries`] candidates were drawn.
+///
+/// Boards starting with a complete row or column are skipped. Candidates are solved with
+/// `options` counting lines cleared, so the same seed and settings always give the same puzzles.
+/// Candidates whose search hits the limits of `options` are skipped, and once
+/// [`SolveOptions::cancel`] is cancelled the puzzles found so far are returned.
+///
+/// # Errors
+/// [`SolveError::NoPieces`] when the catalogue cannot deal, or the error of a malformed candidate,
+/// for example a board too large for the requested backend.
+#[instrument(skip_all)]
+pub fn generate(generation: &Generation, options: &SolveOptions) -> Result<Vec<BlockBlastPuzzle>, SolveError> {
+    let weights: Vec<u64> = generation.catalogue.entries.iter().map(|entry| u64::from(entry.weight)).collect();
+    if weights.iter().sum::<u64>() == 0 || generation.deal == 0 {
+        return Err(SolveError::NoPieces);
+    }
+    let (rows, cols) = (generation.rows, generation.cols);
+    let options_lines = SolveOptions { scoring: Arc::new(LinesCleared), tiebreak: None, lookahead: None, ..options.clone() };
+    // Void cells count as filled, but a line of void cells only is never complete
+    let void = generation.rules.void.clone().unwrap_or_else(|| Array::from_elem((rows, cols), false));
+    let complete = |line: ArrayView1<'_, bool>, dead: ArrayView1<'_, bool>| {
+        line.iter().zip(dead).all(|(&k, &cell_dead)| k || cell_dead) && dead.iter().any(|&cell_dead| !cell_dead)
+    };
+    let mut rng = Rng::new(generation.seed);
+    let mut puzzles = Vec::with_capacity(generation.count);
+    for attempt in 0..generation.tries {
+        if puzzles.len() == generation.count {
+            break;
+        }
+        let board = Array::from_shape_simple_fn((rows, cols), || rng.below(100) < generation.density);
+        let pieces = repeat_with(|| rng.weighted(&weights)).take(generation.deal)
+            .map(|i| generation.catalogue.entries[i].layout.clone()).collect();
+        let full_row = board.rows().into_iter().zip(void.rows()).any(|(row, dead)| complete(row, dead));
+        let full_col = board.columns().into_iter().zip(void.columns()).any(|(col, dead)| complete(col, dead));
+        if full_row || full_col {
+            continue;
+        }
+        let puzzle = BlockBlastPuzzle { board, pieces, rules: generation.rules.clone(), combo: Combo::default(), fixed: Vec::new() };
+        let kept = match satisfies(&puzzle, generation.constraint, &options_lines) {
+            Ok(kept) => kept,
+            Err(SolveError::Interrupted) if options.cancel.is_cancelled() => {
+                debug!(?attempt, found = puzzles.len(), "Cancelled");
+                return Ok(puzzles);
+            },
+            Err(SolveError::Interrupted) => {
+                debug!(?attempt, "Interrupted");
+                false
+            },
+            Err(e) => return Err(e),
+        };
+        if kept {
+            debug!(?attempt, found = puzzles.len() + 1);
+            puzzles.push(puzzle);
+        }
+    }
+    if puzzles.len() < generation.count {
+        debug!(found = puzzles.len(), wanted = generation.count, "Out of tries");
+    }
+    Ok(puzzles)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn limited_generation_keeps_constraints() {
+        let limited = SolveOptions { node_limit: Some(2000), ..SolveOptions::default() };
+        let unlimited = SolveOptions { scoring: Arc::new(LinesCleared), ..SolveOptions::default() };
+        for constraint in [Constraint::Solvable, Constraint::Unsolvable, Constraint::Clears(0), Constraint::Clears(1), Constraint::NearMiss] {
+            let generation = Generation { constraint, count: 3, tries: 2000, density: 20, seed: 7, ..Generation::default() };
+            let puzzles = generate(&generation, &limited).unwrap();
+            assert!(!puzzles.is_empty(), "no puzzle with {constraint:?}");
+            for puzzle in &puzzles {
+                assert!(satisfies(puzzle, constraint, &unlimited).unwrap(), "{constraint:?} does not hold for {puzzle:?}");
+            }
+        }
+    }
+}
@@ -127,6 +127,7 @@ Row string input with insufficient length will leave the remaining cells valid."
                     break;
                 }
                 match c {
+                    #[expect(clippy::collapsible_match, reason = "only the first start is kept, later ones are ignored")]
                     'S' | 's' => {
                         // Start is a valid, unvisited cell
                         if !start_found {
@@ -0,0 +1,262 @@
+use crate::{BlockBlastPuzzle, Chunk, Placement, Region, Rules, Solution, SolveError};
+use crate::catalogue::Catalogue;
+use crate::scoring::Combo;
+use crate::risk::Report;
+use crate::simulate::Spread;
+use ndarray::prelude::*;
+use serde::{Deserialize, Serialize};
+/// Version of the documents below, bumped on every incompatible change.
+pub const SCHEMA_VERSION: u32 = 1;
+/// A piece given by its rows or by its name in the standard catalogue.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PieceDoc {
+    Rows(Vec<String>),
+    Name(String),
+}
+/// A named region of a [`PuzzleDoc`], cleared once all of its cells are filled.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RegionDoc {
+    pub name: String,
+    /// Row and column of every cell.
+    pub cells: Vec<[usize; 2]>,
+}
+/// A placement decided before solving, see [`BlockBlastPuzzle::fixed`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlacementDoc {
+    /// Index of the piece in the input, from 0.
+    pub piece: usize,
+    /// Row and column of the top-left corner of the trimmed piece layout.
+    pub row: usize,
+    pub col: usize,
+}
+/// A puzzle. Rows of cells are strings where `.` is empty and any other character is filled, except
+/// `X` on the board for a void cell that can never be filled.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PuzzleDoc {
+    pub version: u32,
+    pub board: Vec<String>,
+    /// Chunk rows and columns, also clearing filled chunks when given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk: Option<[usize; 2]>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub regions: Vec<RegionDoc>,
+    pub pieces: Vec<PieceDoc>,
+    /// Placements played in this order before the other pieces are searched.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fixed: Vec<PlacementDoc>,
+}
+/// One placement of a [`SolutionDoc`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StepDoc {
+    /// Index of the piece in the input, from 0.
+    pub piece: usize,
+    /// Row and column of the top-left corner of the piece layout.
+    pub row: usize,
+    pub col: usize,
+    /// Lines, chunks and regions cleared.
+    pub clears: usize,
+    /// Indices of the regions among the clears, from 0.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub regions: Vec<usize>,
+    pub score: usize,
+    /// Board after the placement and its clears, `#` filled, `.` empty and `X` void.
+    pub board: Vec<String>,
+    /// The placement was fixed in the puzzle rather than searched.
+    #[serde(default, skip_serializing_if = "core::ops::Not::not")]
+    pub fixed: bool,
+}
+/// One placement sequence.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RankedDoc {
+    pub clears: usize,
+    pub score: usize,
+    pub steps: Vec<StepDoc>,
+}
+/// The result of a solve. Without a solution the totals are 0 and there are no steps.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SolutionDoc {
+    pub version: u32,
+    pub solvable: bool,
+    pub clears: usize,
+    pub score: usize,
+    pub steps: Vec<StepDoc>,
+    /// The search was stopped by a limit or cancelled before it could prove the solutions the
+    /// best, or before it found any.
+    #[serde(default, skip_serializing_if = "core::ops::Not::not")]
+    pub unproven: bool,
+    /// Why there is no solution.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Best solutions with distinct final boards when several were asked for, the first one
+    /// repeating the totals and steps above.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ranked: Option<Vec<RankedDoc>>,
+}
+/// One puzzle of a batch, a line of its own in JSON Lines output.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordDoc {
+    /// Index of the puzzle in the input, from 0.
+    pub index: usize,
+    /// Time taken by the solve.
+    pub micros: usize,
+    /// Search nodes of the best solution, 0 without one.
+    pub nodes: usize,
+    #[serde(flatten)]
+    pub solution: SolutionDoc,
+}
+/// Totals over a batch, the last line of its JSON Lines output.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SummaryDoc {
+    pub version: u32,
+    pub puzzles: usize,
+    pub solved: usize,
+    /// Indices of the puzzles whose pieces cannot all be placed.
+    pub unsolvable: Vec<usize>,
+    /// Indices of the puzzles that could not be read or are malformed.
+    pub invalid: Vec<usize>,
+    /// Indices of the puzzles whose search was stopped by a limit or cancelled, solved or not.
+    #[serde(default)]
+    pub unproven: Vec<usize>,
+    /// Time taken by the whole batch.
+    pub wall_micros: usize,
+    /// Time taken by every solve.
+    pub micros: Spread,
+    /// Search nodes of every solved puzzle.
+    pub nodes: Spread,
+}
+/// How dangerous a board is, see [`crate::risk::analyze`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnalysisDoc {
+    pub version: u32,
+    /// Total weight of the deals tried.
+    pub deals: u64,
+    /// Weight of the deals whose pieces can all be placed.
+    pub placeable: u64,
+    /// Weight of the deals left unchecked because the analysis was cancelled.
+    #[serde(default)]
+    pub unknown: u64,
+    /// Names of the catalogue pieces that fit nowhere.
+    pub unfit: Vec<String>,
+    /// Row and column of every empty cell no piece can fill.
+    pub dead: Vec<[usize; 2]>,
+}
+/// Cells of `rows` for which `cell` holds, missing ones read as `.`.
+fn grid(rows: &[String], cell: impl Fn(char) -> bool) -> Array2<bool> {
+    let cols = rows.iter().map(|row| row.chars().count()).max().unwrap_or(0);
+    let mut grid = Array::from_elem((rows.len(), cols), cell('.'));
+    for (i, row) in rows.iter().enumerate() {
+        for (j, ch) in row.chars().enumerate() {
+            grid[[i, j]] = cell(ch);
+        }
+    }
+    grid
+}
+/// Rows of `grid` in the `#` and `.` of the documents, cells of `void` as `X`.
+fn rows(grid: &Array2<bool>, void: Option<&Array2<bool>>) -> Vec<String> {
+    grid.rows().into_iter().enumerate().map(|(i, row)| row.iter().enumerate().map(|(j, &cell)| {
+        if void.is_some_and(|mask| mask[[i, j]]) {'X'} else if cell {'#'} else {'.'}
+    }).collect()).collect()
+}
+impl PuzzleDoc {
+    /// Parses a puzzle document.
+    ///
+    /// # Errors
+    /// Returns a message when the JSON is malformed or of another schema version.
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let doc: Self = match serde_json::from_str(json) {
+            Ok(doc) => doc,
+            Err(e) => return Err(e.to_string()),
+        };
+        if doc.version != SCHEMA_VERSION {
+            return Err(format!("unsupported schema version {}, expected {SCHEMA_VERSION}", doc.version));
+        }
+        Ok(doc)
+    }
+    /// The puzzle described, rows shorter than the longest one are padded with empty cells.
+    ///
+    /// # Errors
+    /// Returns a message when a piece name is not in the standard catalogue.
+    pub fn to_puzzle(&self) -> Result<BlockBlastPuzzle, String> {
+        let catalogue = Catalogue::standard();
+        let mut pieces = Vec::with_capacity(self.pieces.len());
+        for piece in &self.pieces {
+            match piece.clone() {
+                PieceDoc::Rows(layout) => pieces.push(grid(&layout, |ch| ch != '.')),
+                PieceDoc::Name(name) => match catalogue.get(&name) {
+                    Some(entry) => pieces.push(entry.layout.clone()),
+                    None => return Err(format!("unknown piece {name:?}")),
+                },
+            }
+        }
+        let chunk = self.chunk.map(|[r, c]| Chunk(r, c));
+        let board = grid(&self.board, |ch| ch != '.' && ch != 'X');
+        let void = self.board.iter().any(|row| row.contains('X')).then(|| grid(&self.board, |ch| ch == 'X'));
+        let regions = self.regions.iter().map(|region| Region {
+            name: region.name.clone(),
+            cells: region.cells.iter().map(|&[r, c]| (r, c)).collect(),
+        }).collect();
+        let fixed = self.fixed.iter().map(|&PlacementDoc { piece, row, col }| Placement { piece, row, col }).collect();
+        Ok(BlockBlastPuzzle { board, pieces, rules: Rules { chunk, void, regions, ..Rules::default() }, combo: Combo::default(), fixed })
+    }
+}
+impl AnalysisDoc {
+    /// Document of `report`, naming its pieces after the entries of `catalogue`.
+    #[must_use]
+    pub fn new(report: &Report, catalogue: &Catalogue) -> Self {
+        Self {
+            version: SCHEMA_VERSION,
+            deals: report.deals,
+            placeable: report.placeable,
+            unknown: report.unknown,
+            unfit: report.unfit.iter().map(|&i| catalogue.entries[i].name.clone()).collect(),
+            dead: report.dead.iter().map(|&(r, c)| [r, c]).collect(),
+        }
+    }
+}
+impl RankedDoc {
+    /// Document of `solution`, on a board with the `void` cells of its rules.
+    #[must_use]
+    pub fn new(solution: &Solution, void: Option<&Array2<bool>>) -> Self {
+        let steps = solution.steps.iter().map(|step| StepDoc {
+            piece: step.piece,
+            row: step.row,
+            col: step.col,
+            clears: step.clears,
+            regions: step.regions.clone(),
+            score: step.score,
+            board: rows(&step.board, void),
+            fixed: step.fixed,
+        }).collect();
+        Self { clears: solution.clears, score: solution.score, steps }
+    }
+}
+impl SolutionDoc {
+    /// Document of the best of `solutions`, listing all of them when `ranked`, on a board with the
+    /// `void` cells of its rules.
+    #[must_use]
+    pub fn new(result: Result<&[Solution], &SolveError>, ranked: bool, void: Option<&Array2<bool>>) -> Self {
+        let unproven = match result {
+            Ok(solutions) => solutions.iter().any(|solution| !solution.optimal),
+            Err(e) => matches!(e, &SolveError::Interrupted),
+        };
+        let (docs, error) = match result {
+            Ok(solutions) => (solutions.iter().map(|solution| RankedDoc::new(solution, void)).collect(), None),
+            Err(e) => (Vec::new(), Some(e.to_string())),
+        };
+        let best = docs.first().cloned().unwrap_or(RankedDoc { clears: 0, score: 0, steps: Vec::new() });
+        Self {
+            version: SCHEMA_VERSION,
+            solvable: error.is_none() && !docs.is_empty(),
+            clears: best.clears,
+            score: best.score,
+            steps: best.steps,
+            unproven,
+            error,
+            ranked: ranked.then_some(docs),
+        }
+    }
+}
@@ -0,0 +1,537 @@
+//! Block Blast! solver library.
+//!
+//! Describe a round with a [`BlockBlastPuzzle`] and hand it to [`solve`] (or [`solve_with`] for
+//! non-default search options) to get the placement sequence clearing the most lines, or scoring
+//! the most under another [`scoring::Scoring`], or to [`solve_top_k`] for several alternatives.
+//! Whole games can be played with [`simulate::simulate`], and many rounds solved at once with
+//! [`batch::solve`].
+extern crate alloc;
+pub mod scoring;
+pub mod eval;
+pub mod catalogue;
+pub mod lookahead;
+pub mod simulate;
+pub mod screenshot;
+pub mod json;
+pub mod batch;
+pub mod piece;
+pub mod render;
+pub mod risk;
+pub mod generate;
+mod rng;
+mod permutation;
+mod board;
+mod bitboard;
+mod search;
+mod table;
+use board::{Board, ArrayBoard};
+use bitboard::BitBoard;
+use scoring::{Combo, LinesCleared, Scoring};
+use eval::{Evaluator, Weights};
+use lookahead::{Lookahead, Outlook};
+use search::{Params, Stop};
+use alloc::sync::Arc;
+use tracing::*;
+use ndarray::prelude::*;
+use core::{fmt, mem};
+use core::error::Error;
+use core::num::NonZeroUsize;
+use core::str::FromStr;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+use std::time::Instant;
+use std::thread;
+/// Subgrid ("chunk") dimensions, rows by columns, for the wooden variant.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Chunk(pub usize, pub usize);
+#[derive(Copy, Clone, Debug)]
+struct Available(usize, usize);
+impl Available {
+    const fn new(rows: usize, cols: usize, r: usize, c: usize) -> Self {
+        let avail_r = rows - r + 1;
+        let avail_c = cols - c + 1;
+        let avail_len = avail_r * avail_c;
+        Self(avail_c, avail_len)
+    }
+}
+/// Named set of cells, cleared like a line once all of them are filled.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub name: String,
+    /// Row and column of every cell.
+    pub cells: Vec<(usize, usize)>,
+}
+/// Parses `name row,col row,col ...`.
+impl FromStr for Region {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let Some(name) = words.next() else {
+            return Err("expected a region name and its cells".to_owned());
+        };
+        let mut cells = Vec::new();
+        for word in words {
+            match word.split_once(',').map(|(r, c)| (r.parse::<usize>(), c.parse::<usize>())) {
+                Some((Ok(r), Ok(c))) => cells.push((r, c)),
+                _ => return Err(format!("expected ROW,COL, got {word:?}")),
+            }
+        }
+        Ok(Self { name: name.to_owned(), cells })
+    }
+}
+/// A piece put at an anchor, the top-left corner of its trimmed layout.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Placement {
+    /// Index of the piece in [`BlockBlastPuzzle::pieces`].
+    pub piece: usize,
+    pub row: usize,
+    pub col: usize,
+}
+/// When the lines completed by a placement are cleared.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Resolution {
+    /// Every complete line, chunk and region is found first, then all of them are cleared.
+    #[default]
+    Simultaneous,
+    /// Complete rows are cleared first, then columns, chunks and regions, each on the board the
+    /// previous ones left, so a column crossing a cleared row is no longer complete.
+    Cascading,
+}
+/// Clearing rules of the board. The default clears rows and columns at once, as Block Blast! and
+/// 1010! do.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rules {
+    /// Clear completely filled rows.
+    pub clear_rows: bool,
+    /// Clear completely filled columns.
+    pub clear_cols: bool,
+    /// Clear completely filled chunks and regions.
+    pub clear_regions: bool,
+    pub resolution: Resolution,
+    /// Also clear completely filled subgrids of this size.
+    pub chunk: Option<Chunk>,
+    /// Cells that can never be filled nor cleared, the size of the board. Pieces cannot cover them
+    /// and a line or chunk is complete once all its other cells are filled.
+    pub void: Option<Array2<bool>>,
+    /// Also clear these regions once completely filled, a region made of void cells only never.
+    pub regions: Vec<Region>,
+}
+impl Default for Rules {
+    fn default() -> Self {
+        Self { clear_rows: true, clear_cols: true, clear_regions: true, resolution: Resolution::default(), chunk: None, void: None, regions: Vec::new() }
+    }
+}
+impl Rules {
+    /// Woodoku: rows, columns and 3x3 chunks of the 9x9 board.
+    #[must_use]
+    pub fn woodoku() -> Self {
+        Self { chunk: Some(Chunk(3, 3)), ..Self::default() }
+    }
+    /// Rows only, as in Tetris-like games.
+    #[must_use]
+    pub fn rows_only() -> Self {
+        Self { clear_cols: false, ..Self::default() }
+    }
+}
+/// A single round: the current board, the pieces dealt and the rules to play them by.
+#[derive(Clone, Debug)]
+pub struct BlockBlastPuzzle {
+    /// Filled cells of the board.
+    pub board: Array2<bool>,
+    /// Layouts of the dealt pieces, filled cells are `true`. Empty border rows and columns are
+    /// trimmed before solving, and the anchors of the steps refer to the trimmed layouts.
+    pub pieces: Vec<Array2<bool>>,
+    pub rules: Rules,
+    /// Combo state carried over from the previous rounds.
+    pub combo: Combo,
+    /// Placements already decided, played in this order with their clears before the remaining
+    /// pieces are searched.
+    pub fixed: Vec<Placement>,
+}
+/// Board representation used by the search.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Backend {
+    /// Bitboard when the board has at most 256 cells, ndarray otherwise.
+    #[default]
+    Auto,
+    /// One `bool` per cell, works for any board size.
+    Ndarray,
+    /// One bit per cell in 64 or 256 bits, fails on larger boards.
+    Bitboard,
+}
+/// Knobs of the search that do not change the game itself.
+#[derive(Clone, Debug)]
+pub struct SolveOptions {
+    /// Use Steinhaus-Johnson-Trotter algorithm for permutations instead of Heap's.
+    pub sjt: bool,
+    pub backend: Backend,
+    /// Objective to maximize, [`LinesCleared`] by default.
+    pub scoring: Arc<dyn Scoring>,
+    /// Rank solutions of equal score by the quality of their final board instead of keeping the
+    /// first one found.
+    pub tiebreak: Option<Weights>,
+    /// Pick the best of several solutions by how the next round is expected to go.
+    pub lookahead: Option<Lookahead>,
+    /// Worker threads of the search, 0 for one per available core. The result does not depend on
+    /// it.
+    pub threads: usize,
+    /// Memory cap of the transposition table, in bytes, 0 to search without one.
+    pub table_bytes: usize,
+    /// Stop searching after this long, counted from the call to [`solve_with`] or [`solve_top_k`]
+    /// and covering the searches of a lookahead.
+    pub time_limit: Option<Duration>,
+    /// Stop a search after trying about this many placements.
+    pub node_limit: Option<usize>,
+    /// Stop searching once cancelled.
+    pub cancel: CancelToken,
+}
+impl Default for SolveOptions {
+    fn default() -> Self {
+        Self {
+            sjt: false,
+            backend: Backend::default(),
+            scoring: Arc::new(LinesCleared),
+            tiebreak: None,
+            lookahead: None,
+            threads: 1,
+            table_bytes: 64 << 20,
+            time_limit: None,
+            node_limit: None,
+            cancel: CancelToken::default(),
+        }
+    }
+}
+/// Flag shared between clones that stops the searches of [`SolveOptions::cancel`], for example from
+/// a signal handler or another thread.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+impl CancelToken {
+    /// Makes every search using this token or a clone of it stop with its best solution so far.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+/// Counters of the search.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Placements tried that fit.
+    pub nodes: usize,
+    /// Partial sequences cut because they could not beat the best ones.
+    pub pruned: usize,
+    /// Positions looked up in the transposition table.
+    pub table_lookups: usize,
+    /// Lookups that found the position.
+    pub table_hits: usize,
+}
+/// One placement of a solution.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Step {
+    /// Index of the placed piece in [`BlockBlastPuzzle::pieces`].
+    pub piece: usize,
+    /// Row of the top-left corner of the piece layout.
+    pub row: usize,
+    /// Column of the top-left corner of the piece layout.
+    pub col: usize,
+    /// Lines, chunks and regions cleared by this placement.
+    pub clears: usize,
+    /// Indices in [`Rules::regions`] of the regions among the clears.
+    pub regions: Vec<usize>,
+    /// Score of this placement.
+    pub score: usize,
+    /// Board after the placement and its clears, void cells empty.
+    pub board: Array2<bool>,
+    /// Whether the placement was one of [`BlockBlastPuzzle::fixed`] rather than searched.
+    pub fixed: bool,
+}
+/// Best placement sequence found for a puzzle.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Solution {
+    /// Lines, chunks and regions cleared over all steps.
+    pub clears: usize,
+    /// Total score over all steps.
+    pub score: usize,
+    /// Combo state after the last step.
+    pub combo: Combo,
+    /// [`Weights::value`] of the final board when a tie-breaker was used.
+    pub quality: Option<i64>,
+    /// Expected next round when the solution was chosen by a lookahead.
+    pub outlook: Option<Outlook>,
+    /// Whether every search behind the solution ran to the end. A search stopped by a limit or a
+    /// cancellation returns the best solution found so far, which may not be the best there is.
+    pub optimal: bool,
+    pub stats: Stats,
+    /// Placements in the order they have to be played.
+    pub steps: Vec<Step>,
+}
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SolveError {
+    /// The board has no rows or no columns.
+    EmptyBoard,
+    /// No pieces were dealt.
+    NoPieces,
+    /// The piece at this index has no cells.
+    EmptyPiece(usize),
+    /// A trimmed piece layout is larger than the board.
+    InvalidPiece { piece: usize, rows: usize, cols: usize },
+    /// The chunk size is zero or does not distribute over the board evenly.
+    InvalidChunk(Chunk),
+    /// The void mask is not the size of the board.
+    InvalidVoid { rows: usize, cols: usize },
+    /// The region at this index has no cells or a cell outside the board.
+    InvalidRegion(usize),
+    /// The fixed placement at this index names no piece or an already placed one, leaves the
+    /// board or overlaps a filled cell.
+    InvalidPlacement(usize),
+    /// The bitboard backend was requested for a board with more than 256 cells.
+    BoardTooLarge { rows: usize, cols: usize },
+    /// No order and placement fits every piece on the board.
+    Unsolvable,
+    /// The search was stopped before it found any placement of every piece.
+    Interrupted,
+}
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::EmptyBoard => write!(f, "the board is empty"),
+            Self::NoPieces => write!(f, "no pieces were given"),
+            Self::EmptyPiece(piece) => write!(f, "piece {} has no cells", piece+1),
+            Self::InvalidPiece { piece, rows, cols } => write!(f, "piece {} ({rows}x{cols}) does not fit on the board", piece+1),
+            Self::InvalidChunk(Chunk(r, c)) => write!(f, "{r}x{c} chunks do not distribute over the board evenly"),
+            Self::InvalidVoid { rows, cols } => write!(f, "the void mask is {rows}x{cols}, not the size of the board"),
+            Self::InvalidRegion(region) => write!(f, "region {} is empty or leaves the board", region+1),
+            Self::InvalidPlacement(placement) => write!(f, "fixed placement {} is of an unknown or already placed piece, or does not fit on the board", placement+1),
+            Self::BoardTooLarge { rows, cols } => write!(f, "a {rows}x{cols} board does not fit in a bitboard"),
+            Self::Unsolvable => write!(f, "no placement fits every piece"),
+            Self::Interrupted => write!(f, "the search was stopped before any placement of every piece was found"),
+        }
+    }
+}
+#[expect(clippy::missing_trait_methods, reason = "no underlying source error")]
+impl Error for SolveError {}
+/// Solves a puzzle with the default [`SolveOptions`].
+///
+/// # Errors
+/// See [`solve_with`].
+pub fn solve(puzzle: &BlockBlastPuzzle) -> Result<Solution, SolveError> {
+    solve_with(puzzle, &SolveOptions::default())
+}
+/// Finds the order and placement of every piece which scores the most.
+///
+/// Ties are resolved by [`SolveOptions::tiebreak`] if set, then in favour of the first sequence
+/// found in permutation order. Orders that only swap identical pieces around are searched once.
+/// With a [`SolveOptions::lookahead`], the best few solutions are compared by their next round.
+///
+/// A search stopped by [`SolveOptions::time_limit`], [`SolveOptions::node_limit`] or
+/// [`SolveOptions::cancel`] returns the best solution found so far, not [`Solution::optimal`].
+///
+/// # Errors
+/// Returns [`SolveError::Unsolvable`] when the pieces cannot all be placed,
+/// [`SolveError::Interrupted`] when the search was stopped before placing them all, or another
+/// variant when the puzzle itself is malformed.
+#[instrument(skip_all)]
+pub fn solve_with(puzzle: &BlockBlastPuzzle, options: &SolveOptions) -> Result<Solution, SolveError> {
+    solve_until(puzzle, options, deadline(options))
+}
+/// When the time limit of `options` ends, counted from now.
+fn deadline(options: &SolveOptions) -> Option<Instant> {
+    let Some(limit) = options.time_limit else {
+        return None;
+    };
+    Instant::now().checked_add(limit)
+}
+/// [`solve_with`] stopping at `deadline` instead of after the time limit.
+fn solve_until(puzzle: &BlockBlastPuzzle, options: &SolveOptions, deadline: Option<Instant>) -> Result<Solution, SolveError> {
+    let keep = options.lookahead.as_ref().map_or(1, |lookahead| lookahead.candidates.max(1));
+    let solutions = match solve_ranked(puzzle, options, keep, deadline) {
+        Ok(solutions) => solutions,
+        Err(e) => return Err(e),
+    };
+    let solution = if let Some(lookahead) = options.lookahead.as_ref() {
+        lookahead::choose(puzzle, options, lookahead, solutions, deadline)
+    } else {
+        solutions.into_iter().next()
+    };
+    solution.ok_or(SolveError::Unsolvable)
+}
+/// Finds up to `k` (at least one) best placement sequences with distinct final boards, best first.
+///
+/// Solutions are ranked as by [`solve_with`], and of several sequences ending on the same board
+/// only the best one is kept. [`SolveOptions::lookahead`] is not used.
+///
+/// # Errors
+/// Same as [`solve_with`].
+#[instrument(skip_all)]
+pub fn solve_top_k(puzzle: &BlockBlastPuzzle, options: &SolveOptions, k: usize) -> Result<Vec<Solution>, SolveError> {
+    solve_ranked(puzzle, options, k.max(1), deadline(options))
+}
+/// Checks that `board` is not empty and that the chunk, void cells and regions of `rules` fit it.
+fn check_board(board: &Array2<bool>, rules: &Rules) -> Result<(), SolveError> {
+    let (rows, cols) = board.dim();
+    if rows == 0 || cols == 0 {
+        return Err(SolveError::EmptyBoard);
+    }
+    if let Some(chunk) = rules.chunk {
+        let Chunk(r, c) = chunk;
+        if r == 0 || c == 0 || rows % r != 0 || cols % c != 0 {
+            return Err(SolveError::InvalidChunk(chunk));
+        }
+    }
+    if let Some(void) = rules.void.as_ref() && void.dim() != (rows, cols) {
+        let (r, c) = void.dim();
+        return Err(SolveError::InvalidVoid { rows: r, cols: c });
+    }
+    if let Some(region) = rules.regions.iter().position(|region| {
+        region.cells.is_empty() || region.cells.iter().any(|&(r, c)| r >= rows || c >= cols)
+    }) {
+        return Err(SolveError::InvalidRegion(region));
+    }
+    Ok(())
+}
+/// Best `keep` solutions with distinct final boards, best first.
+fn solve_ranked(puzzle: &BlockBlastPuzzle, options: &SolveOptions, keep: usize, deadline: Option<Instant>) -> Result<Vec<Solution>, SolveError> {
+    let (rows, cols) = puzzle.board.dim();
+    let n_pieces = puzzle.pieces.len();
+    if let Err(e) = check_board(&puzzle.board, &puzzle.rules) {
+        return Err(e);
+    }
+    if n_pieces == 0 {
+        return Err(SolveError::NoPieces);
+    }
+    // Empty borders would keep the piece off the edges of the board for nothing
+    let trimmed = BlockBlastPuzzle {
+        board: puzzle.board.clone(),
+        pieces: puzzle.pieces.iter().map(piece::trim).collect(),
+        rules: puzzle.rules.clone(),
+        combo: puzzle.combo,
+        fixed: puzzle.fixed.clone(),
+    };
+    let mut piece_avail: Vec<Available> = Vec::with_capacity(n_pieces);
+    for (piece, layout) in trimmed.pieces.iter().enumerate() {
+        let (r, c) = layout.dim();
+        if r == 0 {
+            return Err(SolveError::EmptyPiece(piece));
+        }
+        if rows < r || cols < c {
+            return Err(SolveError::InvalidPiece { piece, rows: r, cols: c });
+        }
+        piece_avail.push(Available::new(rows, cols, r, c));
+    }
+    if !trimmed.fixed.is_empty() {
+        return solve_rest(&trimmed, &piece_avail, options, keep, deadline);
+    }
+    let backend = match options.backend {
+        Backend::Auto if BitBoard::<4>::fits(rows, cols) => Backend::Bitboard,
+        Backend::Auto => Backend::Ndarray,
+        Backend::Bitboard if !BitBoard::<4>::fits(rows, cols) => return Err(SolveError::BoardTooLarge { rows, cols }),
+        backend @ (Backend::Ndarray | Backend::Bitboard) => backend,
+    };
+    debug!(?backend);
+    if backend == Backend::Ndarray {
+        run(ArrayBoard::new(rows, cols, &trimmed.pieces, &piece_avail, &trimmed.rules), &trimmed, &piece_avail, options, keep, deadline)
+    } else if BitBoard::<1>::fits(rows, cols) {
+        run(BitBoard::<1>::new(rows, cols, &trimmed.pieces, &piece_avail, &trimmed.rules), &trimmed, &piece_avail, options, keep, deadline)
+    } else {
+        run(BitBoard::<4>::new(rows, cols, &trimmed.pieces, &piece_avail, &trimmed.rules), &trimmed, &piece_avail, options, keep, deadline)
+    }
+}
+/// Plays the fixed placements of `puzzle`, then searches the best `keep` ways to place the other
+/// pieces and puts the fixed steps in front of each of them.
+fn solve_rest(puzzle: &BlockBlastPuzzle, piece_avail: &[Available], options: &SolveOptions, keep: usize, deadline: Option<Instant>) -> Result<Vec<Solution>, SolveError> {
+    let (rows, cols) = puzzle.board.dim();
+    let mut board = ArrayBoard::new(rows, cols, &puzzle.pieces, piece_avail, &puzzle.rules);
+    let mut state = board.load(&puzzle.board);
+    let mut next = state.clone();
+    let mut placed = vec![false; puzzle.pieces.len()];
+    let mut combo = puzzle.combo;
+    let mut fixed: Vec<Step> = Vec::with_capacity(puzzle.fixed.len());
+    for (index, &Placement { piece, row, col }) in puzzle.fixed.iter().enumerate() {
+        let Some(&Available(avail_c, avail_len)) = piece_avail.get(piece) else {
+            return Err(SolveError::InvalidPlacement(index));
+        };
+        // Checked before computing the anchor, which could overflow on absurd coordinates
+        if placed[piece] || col >= avail_c || row >= avail_len / avail_c {
+            return Err(SolveError::InvalidPlacement(index));
+        }
+        let pos = row * avail_c + col;
+        let Some(clears) = board.place(&state, &mut next, piece, pos) else {
+            return Err(SolveError::InvalidPlacement(index));
+        };
+        placed[piece] = true;
+        mem::swap(&mut state, &mut next);
+        let cells = puzzle.pieces[piece].iter().filter(|&&x| x).count();
+        let score = options.scoring.score(&mut combo, cells, clears);
+        let mut grid = board.store(&state);
+        if let Some(void) = puzzle.rules.void.as_ref() {
+            grid.zip_mut_with(void, |cell, &dead| *cell &= !dead);
+        }
+        fixed.push(Step { piece, row, col, clears, regions: board.cleared_regions(), score, board: grid, fixed: true });
+    }
+    let clears: usize = fixed.iter().map(|step| step.clears).sum();
+    let score: usize = fixed.iter().map(|step| step.score).sum();
+    let final_board = fixed.last().map_or_else(|| puzzle.board.clone(), |step| step.board.clone());
+    // Indices in the puzzle of the pieces left to search
+    let rest: Vec<usize> = (0..puzzle.pieces.len()).filter(|&piece| !placed[piece]).collect();
+    if rest.is_empty() {
+        let quality = options.tiebreak.map(|weights| weights.value(&Evaluator::default().evaluate(&board.store(&state))));
+        return Ok(vec![Solution { clears, score, combo, quality, outlook: None, optimal: true, stats: Stats::default(), steps: fixed }]);
+    }
+    let remaining = BlockBlastPuzzle {
+        board: final_board,
+        pieces: rest.iter().map(|&piece| puzzle.pieces[piece].clone()).collect(),
+        rules: puzzle.rules.clone(),
+        combo,
+        fixed: Vec::new(),
+    };
+    let solutions = match solve_ranked(&remaining, options, keep, deadline) {
+        Ok(solutions) => solutions,
+        Err(e) => return Err(e),
+    };
+    Ok(solutions.into_iter().map(|solution| {
+        let mut steps = fixed.clone();
+        steps.extend(solution.steps.into_iter().map(|step| Step { piece: rest[step.piece], ..step }));
+        Solution { clears: clears + solution.clears, score: score + solution.score, steps, ..solution }
+    }).collect())
+}
+fn run<B: Board>(mut board: B, puzzle: &BlockBlastPuzzle, piece_avail: &[Available], options: &SolveOptions, keep: usize, deadline: Option<Instant>) -> Result<Vec<Solution>, SolveError> {
+    let initial = board.load(&puzzle.board);
+    let shapes: Vec<usize> = puzzle.pieces.iter().map(|layout| {
+        puzzle.pieces.iter().position(|other| other == layout).unwrap()
+    }).collect();
+    let piece_cells: Vec<usize> = puzzle.pieces.iter().map(|layout| layout.iter().filter(|&&x| x).count()).collect();
+    let threads = if options.threads == 0 {
+        thread::available_parallelism().map_or(1, NonZeroUsize::get)
+    } else {
+        options.threads
+    };
+    let stop = Stop::new(deadline, options.node_limit, options.cancel.clone());
+    let params = Params { piece_avail, piece_cells: &piece_cells, shapes: &shapes, sjt: options.sjt, scoring: &*options.scoring, combo: puzzle.combo, tiebreak: options.tiebreak, keep, threads, table_bytes: options.table_bytes, stop: &stop };
+    let (ranked, stats) = search::search(&mut board, &initial, &params);
+    let optimal = !stop.stopped();
+    if ranked.is_empty() {
+        return Err(if optimal {SolveError::Unsolvable} else {SolveError::Interrupted});
+    }
+    let (rows, cols) = puzzle.board.dim();
+    // Replaying the steps on the ndarray board tells which regions each of them cleared
+    let mut replay = ArrayBoard::new(rows, cols, &puzzle.pieces, piece_avail, &puzzle.rules);
+    let mut scratch = replay.load(&puzzle.board);
+    Ok(ranked.into_iter().map(|best| {
+        let steps = best.states.iter().enumerate().map(|(i, state)| {
+            let piece = best.piece_order[i];
+            let avail_c = piece_avail[piece].0;
+            let pos = best.place_order[i];
+            let before = replay.load(&board.store(if i == 0 {&initial} else {&best.states[i-1]}));
+            replay.place(&before, &mut scratch, piece, pos);
+            let regions = replay.cleared_regions();
+            let mut grid = board.store(state);
+            if let Some(void) = puzzle.rules.void.as_ref() {
+                grid.zip_mut_with(void, |cell, &dead| *cell &= !dead);
+            }
+            Step { piece, row: pos / avail_c, col: pos % avail_c, clears: best.lines_cleared[i], regions, score: best.scores[i], board: grid, fixed: false }
+        }).collect();
+        Solution { clears: best.lines_cleared.iter().sum(), score: best.score, combo: best.combo, quality: best.quality, outlook: None, optimal, stats, steps }
+    }).collect())
+}
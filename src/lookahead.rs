@@ -0,0 +1,78 @@
+use crate::{BlockBlastPuzzle, Solution, SolveError, SolveOptions, solve_until};
+use crate::catalogue::Catalogue;
+use tracing::*;
+use std::time::Instant;
+/// Settings of the next-round lookahead.
+#[derive(Clone, Debug)]
+pub struct Lookahead {
+    /// Distribution of the pieces of the next deal.
+    pub catalogue: Catalogue,
+    /// Pieces per deal.
+    pub deal: usize,
+    /// Deals sampled for every candidate, or `None` to enumerate every possible deal.
+    pub samples: Option<usize>,
+    /// Seed of the sampled deals.
+    pub seed: u64,
+    /// How many of the best placement sequences of this round are compared.
+    pub candidates: usize,
+    /// Score lost when the next deal cannot be placed.
+    pub penalty: usize,
+}
+impl Default for Lookahead {
+    fn default() -> Self {
+        Self { catalogue: Catalogue::standard(), deal: 3, samples: Some(32), seed: 0, candidates: 8, penalty: 100 }
+    }
+}
+/// How the round after a solution is expected to go, every quantity weighted by the probability
+/// of the deal it comes from.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Outlook {
+    /// Total weight of the deals considered.
+    pub deals: u64,
+    /// Weight of the deals that can be placed.
+    pub placeable: u64,
+    /// Weighted sum of the best score of every placeable deal.
+    pub next_score: u64,
+}
+fn wide(n: usize) -> u64 {
+    u64::try_from(n).unwrap_or(u64::MAX)
+}
+/// Picks the candidate with the best score plus expected score of the next round, where a next
+/// deal that cannot be placed costs [`Lookahead::penalty`]. Ties keep the earlier candidate.
+///
+/// The deals are searched until `deadline` like the round itself; a candidate whose deals were
+/// cut short is not proven optimal.
+#[instrument(skip_all)]
+pub fn choose(puzzle: &BlockBlastPuzzle, options: &SolveOptions, lookahead: &Lookahead, candidates: Vec<Solution>, deadline: Option<Instant>) -> Option<Solution> {
+    let deals = lookahead.catalogue.deals(lookahead.deal, lookahead.samples, lookahead.seed);
+    let next_options = SolveOptions { tiebreak: None, lookahead: None, ..options.clone() };
+    let mut best: Option<(i128, Solution)> = None;
+    debug!(deals = deals.len(), candidates = candidates.len());
+    for mut candidate in candidates {
+        let board = candidate.steps.last().map_or_else(|| puzzle.board.clone(), |step| step.board.clone());
+        let mut outlook = Outlook::default();
+        for dealt in &deals {
+            let (deal, weight) = (&dealt.0, dealt.1);
+            let pieces = deal.iter().map(|&i| lookahead.catalogue.entries[i].layout.clone()).collect();
+            let next = BlockBlastPuzzle { board: board.clone(), pieces, rules: puzzle.rules.clone(), combo: candidate.combo, fixed: Vec::new() };
+            outlook.deals += weight;
+            match solve_until(&next, &next_options, deadline) {
+                Ok(solution) => {
+                    candidate.optimal &= solution.optimal;
+                    outlook.placeable += weight;
+                    outlook.next_score += weight * wide(solution.score);
+                },
+                Err(SolveError::Interrupted) => candidate.optimal = false,
+                Err(_) => {},
+            }
+        }
+        let value = i128::from(outlook.deals) * i128::from(wide(candidate.score)) + i128::from(outlook.next_score)
+            - i128::from(outlook.deals - outlook.placeable) * i128::from(wide(lookahead.penalty));
+        debug!(?value, ?outlook, score = ?candidate.score);
+        candidate.outlook = Some(outlook);
+        if best.as_ref().is_none_or(|&(max, _)| value > max) {
+            best = Some((value, candidate));
+        }
+    }
+    best.map(|(_, solution)| solution)
+}
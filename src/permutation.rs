@@ -0,0 +1,72 @@
+use tracing::*;
+#[derive(Debug)]
+pub struct Permutation {
+    perm: Vec<usize>,
+    pos: Vec<usize>,
+    dir: Vec<bool>,
+    n: usize,
+    sjt: bool,
+}
+impl Permutation {
+    pub fn new(n: usize, sjt: bool) -> Self {
+        let perm: Vec<usize> = (0..n).collect();
+        let mut pos: Vec<usize> = vec![0; n];
+        let dir: Vec<bool> = vec![false; n];
+        if sjt {
+            pos = (0..n).collect();
+        }
+        Self { perm, pos, dir, n, sjt }
+    }
+    // Steinhaus-Johnson-Trotter algorithm (Even's speedup)
+    // https://en.wikipedia.org/wiki/Steinhaus%E2%80%93Johnson%E2%80%93Trotter_algorithm#Even's_speedup
+    fn permute_sjt(&mut self) -> bool {
+        let mut k = self.n - 1;
+        while k > 0 {
+            let c_idx = self.pos[k];
+            let target_idx = if self.dir[k] {
+                let next = c_idx + 1;
+                (next < self.n).then_some(next)
+            } else {
+                c_idx.checked_sub(1)
+            };
+            if let Some(t_idx) = target_idx && self.perm[t_idx] < k {
+                let neighbor_val = self.perm[t_idx];
+                self.perm.swap(c_idx, t_idx);
+                self.pos[k] = t_idx;
+                self.pos[neighbor_val] = c_idx;
+                debug!(l = ?c_idx, r = ?t_idx, perm = ?self.perm, pos = ?self.pos, dir = ?self.dir);
+                return true;
+            }
+            self.dir[k] = !self.dir[k];
+            k -= 1;
+        }
+        false
+    }
+    // Heap's algorithm (non-recursive)
+    // https://en.wikipedia.org/wiki/Heap%27s_algorithm#cite_ref-3
+    fn permute_heap(&mut self) -> bool {
+        let mut i = 1_usize;
+        while i < self.n {
+            if self.pos[i] < i {
+                let l = if (i&1)==0 {0_usize} else {self.pos[i]};
+                self.perm.swap(l, i);
+                self.pos[i] += 1;
+                debug!(?l, r = ?i, perm = ?self.perm, c = ?self.pos);
+                return true;
+            }
+            self.pos[i] = 0;
+            i += 1;
+        }
+        false
+    }
+    pub fn perm(&self) -> &[usize] {
+        &self.perm
+    }
+    pub fn permute(&mut self) -> bool {
+        if self.sjt {
+            self.permute_sjt()
+        } else {
+            self.permute_heap()
+        }
+    }
+}
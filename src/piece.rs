@@ -0,0 +1,37 @@
+use ndarray::prelude::*;
+/// `layout` without its empty border rows and columns, 0x0 when it has no cells.
+#[must_use]
+pub fn trim(layout: &Array2<bool>) -> Array2<bool> {
+    let filled = |(i, j): (usize, usize)| layout[[i, j]];
+    let (rows, cols) = layout.dim();
+    let used_rows: Vec<usize> = (0..rows).filter(|&i| (0..cols).any(|j| filled((i, j)))).collect();
+    let used_cols: Vec<usize> = (0..cols).filter(|&j| (0..rows).any(|i| filled((i, j)))).collect();
+    let (Some(&top), Some(&bottom), Some(&left), Some(&right)) = (used_rows.first(), used_rows.last(), used_cols.first(), used_cols.last()) else {
+        return Array::from_elem((0, 0), false);
+    };
+    layout.slice(s![top..=bottom, left..=right]).to_owned()
+}
+/// Whether the cells of `layout` are orthogonally connected, as those of every dealt piece are.
+/// A layout without cells counts as connected.
+#[must_use]
+pub fn connected(layout: &Array2<bool>) -> bool {
+    let (rows, cols) = layout.dim();
+    let Some((start, _)) = layout.indexed_iter().find(|&(_, &k)| k) else {
+        return true;
+    };
+    let mut seen: Array2<bool> = Array::from_elem((rows, cols), false);
+    seen[start] = true;
+    let mut stack = vec![start];
+    let mut reached = 1_usize;
+    while let Some((i, j)) = stack.pop() {
+        let neighbours = [(i.wrapping_sub(1), j), (i + 1, j), (i, j.wrapping_sub(1)), (i, j + 1)];
+        for (r, c) in neighbours {
+            if r < rows && c < cols && layout[[r, c]] && !seen[[r, c]] {
+                seen[[r, c]] = true;
+                reached += 1;
+                stack.push((r, c));
+            }
+        }
+    }
+    reached == layout.iter().filter(|&&k| k).count()
+}
@@ -0,0 +1,156 @@
+use crate::{BlockBlastPuzzle, Solution};
+use crate::piece;
+use ndarray::prelude::*;
+/// Cells of the board around one step of a solution.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    /// Filled cells before the placement.
+    pub before: Array2<bool>,
+    /// Cells taken by the placed piece.
+    pub placed: Array2<bool>,
+    /// Filled cells removed by the clears of the placement.
+    pub cleared: Array2<bool>,
+    /// Filled cells after the placement and its clears.
+    pub after: Array2<bool>,
+}
+/// Filled cells of the board of `puzzle`, void cells empty as in the steps.
+fn start(puzzle: &BlockBlastPuzzle) -> Array2<bool> {
+    let mut board = puzzle.board.clone();
+    if let Some(void) = puzzle.rules.void.as_ref() {
+        board.zip_mut_with(void, |cell, &dead| *cell &= !dead);
+    }
+    board
+}
+/// One [`Frame`] per step of `solution`, a solution of `puzzle`.
+#[must_use]
+pub fn frames(puzzle: &BlockBlastPuzzle, solution: &Solution) -> Vec<Frame> {
+    let mut before = start(puzzle);
+    solution.steps.iter().map(|step| {
+        let layout = piece::trim(&puzzle.pieces[step.piece]);
+        let mut placed = Array::from_elem(before.dim(), false);
+        for ((i, j), &k) in layout.indexed_iter() {
+            placed[[step.row + i, step.col + j]] = k;
+        }
+        let cleared = Array::from_shape_fn(before.dim(), |cell| (before[cell] || placed[cell]) && !step.board[cell]);
+        let frame = Frame { before: before.clone(), placed, cleared, after: step.board.clone() };
+        before.clone_from(&step.board);
+        frame
+    }).collect()
+}
+const RESET: &str = "\x1b[0m";
+const PLACED: &str = "\x1b[1;32m";
+const CLEARED: &str = "\x1b[1;33;7m";
+const PLACED_CLEARED: &str = "\x1b[1;32;7m";
+const VOID: &str = "\x1b[2m";
+/// Draws `frame` with ANSI colours: the board as the piece lands on the left, its new cells in
+/// green and the cells about to clear inverted, then the board after the clears on the right.
+#[must_use]
+pub fn ansi(frame: &Frame, void: Option<&Array2<bool>>) -> String {
+    let (rows, cols) = frame.after.dim();
+    let mut out = String::new();
+    let mut draw = |colour: &str, ch: char| {
+        if colour.is_empty() {
+            out.push(ch);
+        } else {
+            out.push_str(colour);
+            out.push(ch);
+            out.push_str(RESET);
+        }
+    };
+    for i in 0..rows {
+        for j in 0..cols {
+            let cell = (i, j);
+            if void.is_some_and(|mask| mask[cell]) {
+                draw(VOID, 'X');
+            } else if frame.cleared[cell] {
+                draw(if frame.placed[cell] {PLACED_CLEARED} else {CLEARED}, '#');
+            } else if frame.placed[cell] {
+                draw(PLACED, '#');
+            } else {
+                draw("", if frame.before[cell] {'#'} else {'.'});
+            }
+        }
+        for ch in if i == rows / 2 {"  ->  "} else {"      "}.chars() {
+            draw("", ch);
+        }
+        for j in 0..cols {
+            let cell = (i, j);
+            if void.is_some_and(|mask| mask[cell]) {
+                draw(VOID, 'X');
+            } else {
+                draw("", if frame.after[cell] {'#'} else {'.'});
+            }
+        }
+        draw("", '\n');
+    }
+    out
+}
+/// Side of a cell in SVG pixels.
+const CELL: usize = 20;
+/// Room under every panel for its caption.
+const CAPTION: usize = 28;
+const GAP: usize = 16;
+/// Panels side by side before wrapping to the next row.
+const PANELS_PER_ROW: usize = 4;
+/// Draws the start of `puzzle` and every step of `solution` as panels of a self-contained SVG.
+///
+/// Each step shows the board as its piece lands, the piece in green and the cells it clears
+/// in orange, with its anchor and clears underneath.
+#[must_use]
+pub fn svg(puzzle: &BlockBlastPuzzle, solution: &Solution) -> String {
+    let (rows, cols) = puzzle.board.dim();
+    let (width, height) = (cols * CELL, rows * CELL + CAPTION);
+    let void = puzzle.rules.void.as_ref();
+    let empty = Array::from_elem((rows, cols), false);
+    let start = Frame { before: start(puzzle), placed: empty.clone(), cleared: empty.clone(), after: empty };
+    let mut panels = vec![(start, "Start".to_owned())];
+    for (frame, step) in frames(puzzle, solution).into_iter().zip(&solution.steps) {
+        let clears = match step.clears {
+            0 => String::new(),
+            1 => ", 1 clear".to_owned(),
+            n => format!(", {n} clears"),
+        };
+        let fixed = if step.fixed {" (fixed)"} else {""};
+        panels.push((frame, format!("Piece {} at {},{}{clears}{fixed}", step.piece+1, step.row, step.col)));
+    }
+    let across = panels.len().min(PANELS_PER_ROW);
+    let down = panels.len().div_ceil(PANELS_PER_ROW);
+    let total_width = across * (width + GAP) + GAP;
+    let total_height = down * (height + GAP) + GAP;
+    let mut parts = vec![format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{total_width}\" height=\"{total_height}\" viewBox=\"0 0 {total_width} {total_height}\" font-family=\"sans-serif\" font-size=\"12\">\n\
+         <rect width=\"100%\" height=\"100%\" fill=\"#ffffff\"/>\n"
+    )];
+    for (index, (frame, caption)) in panels.into_iter().enumerate() {
+        let x = GAP + (index % PANELS_PER_ROW) * (width + GAP);
+        let y = GAP + (index / PANELS_PER_ROW) * (height + GAP);
+        parts.push(format!("<g transform=\"translate({x},{y})\">\n"));
+        for ((i, j), &filled) in frame.before.indexed_iter() {
+            let cell = (i, j);
+            let fill = if void.is_some_and(|mask| mask[cell]) {
+                "#303030"
+            } else if frame.cleared[cell] && frame.placed[cell] {
+                "#8bc34a"
+            } else if frame.cleared[cell] {
+                "#ff9800"
+            } else if frame.placed[cell] {
+                "#43a047"
+            } else if filled {
+                "#5c6bc0"
+            } else {
+                "#eceff1"
+            };
+            parts.push(format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{fill}\" stroke=\"#b0bec5\"/>\n", j * CELL, i * CELL, CELL, CELL));
+        }
+        parts.push(format!("<text x=\"0\" y=\"{}\">{caption}</text>\n</g>\n", rows * CELL + CAPTION - 10));
+    }
+    parts.push("</svg>\n".to_owned());
+    parts.concat()
+}
+/// [`svg`] in a self-contained HTML page.
+#[must_use]
+pub fn html(puzzle: &BlockBlastPuzzle, solution: &Solution) -> String {
+    let summary = format!("{} clears, score {}", solution.clears, solution.score);
+    format!("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Block Blast! solution</title>\n</head>\n<body>\n<p>{summary}</p>\n{}</body>\n</html>\n",
+            svg(puzzle, solution))
+}
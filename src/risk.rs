@@ -0,0 +1,158 @@
+use crate::{Available, BlockBlastPuzzle, CancelToken, SolveError, SolveOptions, check_board};
+use crate::board::{ArrayBoard, Board as _, fits_in_order};
+use crate::catalogue::Catalogue;
+use crate::permutation::Permutation;
+use crate::piece;
+use tracing::*;
+use ndarray::prelude::*;
+use core::iter::repeat_with;
+use core::num::NonZeroUsize;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::panic;
+use std::thread;
+/// Settings of [`analyze`].
+#[derive(Clone, Debug)]
+pub struct Analysis {
+    /// Distribution of the dealt pieces.
+    pub catalogue: Catalogue,
+    /// Pieces per deal.
+    pub deal: usize,
+    /// Deals sampled, or `None` to try every possible deal.
+    pub samples: Option<usize>,
+    /// Seed of the sampled deals.
+    pub seed: u64,
+    /// Deals checked at once, 0 for one per core.
+    pub jobs: usize,
+}
+impl Default for Analysis {
+    fn default() -> Self {
+        Self { catalogue: Catalogue::standard(), deal: 3, samples: None, seed: 0, jobs: 0 }
+    }
+}
+/// How dangerous a board is.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    /// Total weight of the deals tried, each weighted by its probability.
+    pub deals: u64,
+    /// Weight of the deals whose pieces can all be placed.
+    pub placeable: u64,
+    /// Weight of the deals left unchecked because the analysis was cancelled.
+    pub unknown: u64,
+    /// Indices in the catalogue of the pieces that fit nowhere on the board.
+    pub unfit: Vec<usize>,
+    /// Empty cells that no placement of a catalogue piece covers.
+    pub dead: Vec<(usize, usize)>,
+}
+/// Whether the `pieces` can all be placed in some order starting from `state`, or `None` when
+/// `cancel` is cancelled before finding out. Orders of identical pieces are only tried once.
+fn placeable(board: &mut ArrayBoard, state: &Array2<bool>, pieces: &[usize], piece_avail: &[Available], cancel: &CancelToken) -> Option<bool> {
+    let mut tried: Vec<Vec<usize>> = Vec::new();
+    let mut permutation = Permutation::new(pieces.len(), false);
+    loop {
+        if cancel.is_cancelled() {
+            return None;
+        }
+        let order: Vec<usize> = permutation.perm().iter().map(|&i| pieces[i]).collect();
+        if !tried.contains(&order) {
+            if fits_in_order(board, state, &order, piece_avail) {
+                return Some(true);
+            }
+            tried.push(order);
+        }
+        if !permutation.permute() {
+            return Some(false);
+        }
+    }
+}
+/// Analyzes the board and rules of `puzzle`, whose pieces are ignored.
+///
+/// Reports which deals of [`Analysis::catalogue`] can be fully placed, stopping at the first order
+/// and placements that fit, which of its pieces fit nowhere, and which empty cells none of them
+/// can fill. Only the cancellation of `options` applies, the deals it leaves unchecked are
+/// reported as [`Report::unknown`].
+///
+/// # Errors
+/// Returns the error of the board when it is malformed.
+#[instrument(skip_all)]
+pub fn analyze(puzzle: &BlockBlastPuzzle, analysis: &Analysis, options: &SolveOptions) -> Result<Report, SolveError> {
+    if let Err(e) = check_board(&puzzle.board, &puzzle.rules) {
+        return Err(e);
+    }
+    let (rows, cols) = puzzle.board.dim();
+    let layouts: Vec<Array2<bool>> = analysis.catalogue.entries.iter().map(|entry| piece::trim(&entry.layout)).collect();
+    // Pieces larger than the board have no anchor at all
+    let sized: Vec<usize> = (0..layouts.len()).filter(|&i| {
+        let (r, c) = layouts[i].dim();
+        r > 0 && r <= rows && c <= cols
+    }).collect();
+    let pieces: Vec<Array2<bool>> = sized.iter().map(|&i| layouts[i].clone()).collect();
+    let piece_avail: Vec<Available> = pieces.iter().map(|layout| {
+        let (r, c) = layout.dim();
+        Available::new(rows, cols, r, c)
+    }).collect();
+    let mut board = ArrayBoard::new(rows, cols, &pieces, &piece_avail, &puzzle.rules);
+    let state = board.load(&puzzle.board);
+    let mut scratch = state.clone();
+    let mut fits = vec![false; layouts.len()];
+    let mut covered: Array2<bool> = Array::from_elem((rows, cols), false);
+    for (piece, layout) in pieces.iter().enumerate() {
+        let Available(avail_c, avail_len) = piece_avail[piece];
+        for pos in 0..avail_len {
+            if board.place(&state, &mut scratch, piece, pos).is_none() {
+                continue;
+            }
+            fits[sized[piece]] = true;
+            let (pos_r, pos_c) = (pos / avail_c, pos % avail_c);
+            for ((i, j), &k) in layout.indexed_iter() {
+                if k {
+                    covered[[pos_r + i, pos_c + j]] = true;
+                }
+            }
+        }
+    }
+    let unfit = (0..layouts.len()).filter(|&i| !fits[i]).collect();
+    let filled = board.store(&state);
+    let dead = covered.indexed_iter().filter(|&(cell, &k)| !k && !filled[cell]).map(|(cell, _)| cell).collect();
+    let dealt = analysis.catalogue.deals(analysis.deal, analysis.samples, analysis.seed);
+    debug!(deals = dealt.len());
+    // Deals with a piece larger than the board cannot be placed
+    let deal_pieces: Vec<Option<Vec<usize>>> = dealt.iter().map(|dealt_one| {
+        dealt_one.0.iter().map(|&i| sized.iter().position(|&piece| piece == i)).collect()
+    }).collect();
+    let workers = if analysis.jobs == 0 {
+        thread::available_parallelism().map_or(1, NonZeroUsize::get)
+    } else {
+        analysis.jobs
+    };
+    let next = AtomicUsize::new(0);
+    let (shared_board, shared_state) = (&board, &state);
+    let checked: Vec<Vec<(usize, Option<bool>)>> = thread::scope(|scope| {
+        let handles: Vec<_> = repeat_with(|| scope.spawn(|| {
+            let mut worker_board = shared_board.clone();
+            let mut verdicts = Vec::new();
+            loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(pieces) = deal_pieces.get(index) else {
+                    return verdicts;
+                };
+                let verdict = pieces.as_ref().map_or(Some(false), |deal| placeable(&mut worker_board, shared_state, deal, &piece_avail, &options.cancel));
+                verdicts.push((index, verdict));
+            }
+        })).take(workers.min(deal_pieces.len())).collect();
+        handles.into_iter().map(|handle| handle.join().unwrap_or_else(|e| panic::resume_unwind(e))).collect()
+    });
+    let mut report = Report { deals: 0, placeable: 0, unknown: 0, unfit, dead };
+    for (index, verdict) in checked.into_iter().flatten() {
+        let weight = dealt[index].1;
+        report.deals += weight;
+        match verdict {
+            Some(true) => report.placeable += weight,
+            Some(false) => {},
+            None => report.unknown += weight,
+        }
+    }
+    if report.unknown > 0 {
+        debug!(unknown = report.unknown, "Cancelled");
+    }
+    Ok(report)
+}
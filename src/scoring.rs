@@ -0,0 +1,70 @@
+use core::fmt;
+/// Combo state carried from one placement to the next, and across rounds.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Combo {
+    /// Consecutive placements that cleared something.
+    pub streak: usize,
+    /// Placements since the last clear.
+    pub idle: usize,
+}
+/// Objective maximized by the search.
+pub trait Scoring: fmt::Debug + Send + Sync {
+    /// Score of one placement of `cells` cells making `clears` clears, updating `combo`.
+    fn score(&self, combo: &mut Combo, cells: usize, clears: usize) -> usize;
+    /// Upper bound on the total score of `pieces` more placements of `cells` cells with at most
+    /// `clears` clears between them, starting from `combo`.
+    fn bound(&self, combo: Combo, pieces: usize, cells: usize, clears: usize) -> usize;
+}
+/// Plain count of cleared lines (and chunks), the original objective.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct LinesCleared;
+impl Scoring for LinesCleared {
+    fn score(&self, _combo: &mut Combo, _cells: usize, clears: usize) -> usize {
+        clears
+    }
+    fn bound(&self, _combo: Combo, _pieces: usize, _cells: usize, clears: usize) -> usize {
+        clears
+    }
+}
+/// Points as the game awards them.
+///
+/// Every placed cell scores, clearing `n` lines at once scores `line * n * (n + 1) / 2`, multiplied
+/// by one more than the combo streak. The streak grows with every clearing placement and is lost
+/// after `grace` placements in a row without a clear.
+#[derive(Copy, Clone, Debug)]
+pub struct Points {
+    pub cell: usize,
+    pub line: usize,
+    pub grace: usize,
+}
+impl Default for Points {
+    fn default() -> Self {
+        Self { cell: 1, line: 10, grace: 3 }
+    }
+}
+impl Points {
+    const fn lines(&self, clears: usize) -> usize {
+        self.line * clears * (clears + 1) / 2
+    }
+}
+impl Scoring for Points {
+    fn score(&self, combo: &mut Combo, cells: usize, clears: usize) -> usize {
+        let mut score = self.cell * cells;
+        if clears == 0 {
+            combo.idle += 1;
+            if combo.idle >= self.grace {
+                combo.streak = 0;
+            }
+        } else {
+            score += self.lines(clears) * (combo.streak + 1);
+            combo.streak += 1;
+            combo.idle = 0;
+        }
+        score
+    }
+    // The line bonus grows faster than linearly, so all clears in the last placement at the
+    // highest multiplier reachable is the best case
+    fn bound(&self, combo: Combo, pieces: usize, cells: usize, clears: usize) -> usize {
+        self.cell * cells + self.lines(clears) * (combo.streak + pieces)
+    }
+}
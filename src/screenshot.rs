@@ -0,0 +1,276 @@
+use ndarray::prelude::*;
+use png::{ColorType, Decoder, Transformations};
+use tracing::*;
+use core::fmt;
+use core::error::Error;
+use core::str::FromStr;
+use std::io::{BufRead, Seek};
+/// Pixel rectangle of a screenshot.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+/// Parses `x,y,width,height`.
+impl FromStr for Rect {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parsed: Result<Vec<usize>, _> = s.split(',').map(|n| n.trim().parse::<usize>()).collect();
+        match parsed.as_deref() {
+            Ok(&[x, y, width, height]) => Ok(Self { x, y, width, height }),
+            Ok(_) => Err(format!("expected x,y,width,height, got {s:?}")),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+impl Rect {
+    const fn right(&self) -> usize {
+        self.x + self.width
+    }
+    const fn bottom(&self) -> usize {
+        self.y + self.height
+    }
+}
+/// Where to look in a screenshot and how to tell blocks apart from the rest.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Calibration {
+    /// Board cells, rows by columns.
+    pub rows: usize,
+    pub cols: usize,
+    /// Board area, the largest panel standing out from the background when `None`.
+    pub grid: Option<Rect>,
+    /// Area holding the offered pieces, everything below the board when `None`.
+    pub tray: Option<Rect>,
+    /// Background colour around the board, the top-left pixel when `None`.
+    pub background: Option<[u8; 3]>,
+    /// Summed channel difference from the background above which a pixel is part of the board.
+    pub tolerance: u32,
+    /// Brightest channel value from which a pixel is part of a block.
+    pub threshold: u8,
+    /// Size of a cell of the offered pieces, in percent of a board cell.
+    pub piece_scale: usize,
+}
+impl Default for Calibration {
+    fn default() -> Self {
+        Self { rows: 8, cols: 8, grid: None, tray: None, background: None, tolerance: 48, threshold: 160, piece_scale: 50 }
+    }
+}
+/// Board and pieces read from a screenshot.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Screenshot {
+    pub board: Array2<bool>,
+    /// Offered pieces from left to right.
+    pub pieces: Vec<Array2<bool>>,
+    /// Board area used.
+    pub grid: Rect,
+}
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ImportError {
+    /// The file is not a readable PNG.
+    Decode(String),
+    /// No board was found, or it is too small for the calibrated cells.
+    NoGrid,
+    /// A calibrated area does not lie within the image.
+    OutOfBounds(Rect),
+    /// Nothing that looks like a piece was found below the board.
+    NoPieces,
+}
+impl fmt::Display for ImportError {
+    #[expect(clippy::ref_patterns, reason = "the decoder message is borrowed from the error")]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Decode(ref e) => write!(f, "cannot decode the screenshot: {e}"),
+            Self::NoGrid => write!(f, "no board found in the screenshot"),
+            Self::OutOfBounds(rect) => write!(f, "area {},{},{},{} lies outside the screenshot", rect.x, rect.y, rect.width, rect.height),
+            Self::NoPieces => write!(f, "no pieces found below the board"),
+        }
+    }
+}
+#[expect(clippy::missing_trait_methods, reason = "no underlying source error")]
+impl Error for ImportError {}
+/// Decoded RGB pixels, row-major.
+struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<[u8; 3]>,
+}
+impl Image {
+    fn decode<R: BufRead + Seek>(reader: R) -> Result<Self, ImportError> {
+        let mut decoder = Decoder::new(reader);
+        decoder.set_transformations(Transformations::normalize_to_color8());
+        let mut png = match decoder.read_info() {
+            Ok(png) => png,
+            Err(e) => return Err(ImportError::Decode(e.to_string())),
+        };
+        let Some(size) = png.output_buffer_size() else {
+            return Err(ImportError::Decode("image too large".to_owned()));
+        };
+        let mut buf = vec![0; size];
+        let info = match png.next_frame(&mut buf) {
+            Ok(info) => info,
+            Err(e) => return Err(ImportError::Decode(e.to_string())),
+        };
+        let (Ok(width), Ok(height)) = (usize::try_from(info.width), usize::try_from(info.height)) else {
+            return Err(ImportError::Decode("image too large".to_owned()));
+        };
+        let samples = match info.color_type {
+            ColorType::Grayscale => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Rgb | ColorType::Indexed => 3,
+            ColorType::Rgba => 4,
+        };
+        let mut pixels = Vec::with_capacity(width * height);
+        for line in buf.chunks(info.line_size).take(height) {
+            pixels.extend(line.chunks(samples).take(width).map(|px| match *px {
+                [v] | [v, _] => [v; 3],
+                [r, g, b] | [r, g, b, _] => [r, g, b],
+                _ => [0; 3],
+            }));
+        }
+        Ok(Self { width, height, pixels })
+    }
+    fn at(&self, x: usize, y: usize) -> [u8; 3] {
+        self.pixels[y * self.width + x]
+    }
+    const fn contains(&self, rect: &Rect) -> bool {
+        rect.width > 0 && rect.height > 0 && rect.right() <= self.width && rect.bottom() <= self.height
+    }
+}
+fn distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    a.iter().zip(b).map(|(&x, y)| u32::from(x.abs_diff(y))).sum()
+}
+/// Whether most of the middle of `rect` is block-coloured.
+fn filled(image: &Image, rect: &Rect, threshold: u8) -> bool {
+    let (dx, dy) = (rect.width / 4, rect.height / 4);
+    let (mut total, mut block) = (0_usize, 0_usize);
+    for y in rect.y + dy..rect.bottom() - dy {
+        for x in rect.x + dx..rect.right() - dx {
+            total += 1;
+            if image.at(x, y).into_iter().max().is_some_and(|v| v >= threshold) {
+                block += 1;
+            }
+        }
+    }
+    block * 2 > total
+}
+/// Bounding boxes and pixel counts of the 8-connected components of `mask` over `area`.
+fn components(area: &Rect, mask: impl Fn(usize, usize) -> bool) -> Vec<(Rect, usize)> {
+    let mut seen = vec![false; area.width * area.height];
+    let mut stack: Vec<(usize, usize)> = Vec::new();
+    let mut found = Vec::new();
+    for y0 in area.y..area.bottom() {
+        for x0 in area.x..area.right() {
+            let idx = (y0 - area.y) * area.width + (x0 - area.x);
+            if seen[idx] || !mask(x0, y0) {
+                continue;
+            }
+            seen[idx] = true;
+            stack.push((x0, y0));
+            let (mut left, mut top, mut right, mut bottom, mut count) = (x0, y0, x0, y0, 0_usize);
+            while let Some((x, y)) = stack.pop() {
+                count += 1;
+                left = left.min(x);
+                right = right.max(x);
+                top = top.min(y);
+                bottom = bottom.max(y);
+                for ny in y.saturating_sub(1).max(area.y)..=(y + 1).min(area.bottom() - 1) {
+                    for nx in x.saturating_sub(1).max(area.x)..=(x + 1).min(area.right() - 1) {
+                        let n_idx = (ny - area.y) * area.width + (nx - area.x);
+                        if !seen[n_idx] && mask(nx, ny) {
+                            seen[n_idx] = true;
+                            stack.push((nx, ny));
+                        }
+                    }
+                }
+            }
+            found.push((Rect { x: left, y: top, width: right - left + 1, height: bottom - top + 1 }, count));
+        }
+    }
+    found
+}
+/// Largest panel that stands out from the background.
+fn detect_grid(image: &Image, calibration: &Calibration) -> Option<Rect> {
+    let background = calibration.background.unwrap_or_else(|| image.at(0, 0));
+    let whole = Rect { x: 0, y: 0, width: image.width, height: image.height };
+    let panels = components(&whole, |x, y| distance(image.at(x, y), background) > calibration.tolerance);
+    panels.into_iter().max_by_key(|&(_, count)| count).map(|(rect, _)| rect)
+}
+/// Reads the board and the offered pieces from a PNG screenshot.
+///
+/// Every board cell is filled when most of its middle is brighter than
+/// [`Calibration::threshold`]. The pieces are the groups of bright pixels below the board, split
+/// into cells of [`Calibration::piece_scale`] times the board cells.
+///
+/// # Errors
+/// Returns an [`ImportError`] when the image cannot be decoded or the board or pieces cannot be
+/// found.
+#[instrument(skip_all)]
+pub fn import<R: BufRead + Seek>(reader: R, calibration: &Calibration) -> Result<Screenshot, ImportError> {
+    let image = match Image::decode(reader) {
+        Ok(image) => image,
+        Err(e) => return Err(e),
+    };
+    let (rows, cols) = (calibration.rows, calibration.cols);
+    let Some(grid) = calibration.grid.or_else(|| detect_grid(&image, calibration)) else {
+        return Err(ImportError::NoGrid);
+    };
+    if !image.contains(&grid) {
+        return Err(ImportError::OutOfBounds(grid));
+    }
+    let (cell_w, cell_h) = (grid.width / cols.max(1), grid.height / rows.max(1));
+    if cell_w == 0 || cell_h == 0 {
+        return Err(ImportError::NoGrid);
+    }
+    debug!(?grid, ?cell_w, ?cell_h);
+    let board = Array::from_shape_fn((rows, cols), |(r, c)| {
+        // Cells split the board evenly, any remainder is spread over the cells
+        let (x, y) = (grid.x + c * grid.width / cols, grid.y + r * grid.height / rows);
+        filled(&image, &Rect { x, y, width: cell_w, height: cell_h }, calibration.threshold)
+    });
+    let tray = calibration.tray.unwrap_or_else(|| Rect { x: 0, y: grid.bottom(), width: image.width, height: image.height - grid.bottom() });
+    if !image.contains(&tray) {
+        return Err(ImportError::OutOfBounds(tray));
+    }
+    let piece_w = (cell_w * calibration.piece_scale / 100).max(1);
+    let piece_h = (cell_h * calibration.piece_scale / 100).max(1);
+    let bright = |x: usize, y: usize| image.at(x, y).into_iter().max().is_some_and(|v| v >= calibration.threshold);
+    // Specks smaller than an eighth of a cell are noise, blocks of one piece are at most half a
+    // cell apart
+    let mut groups: Vec<Rect> = components(&tray, bright).into_iter()
+        .filter(|&(_, count)| count * 8 >= piece_w * piece_h).map(|(rect, _)| rect).collect();
+    let near = |a: &Rect, b: &Rect| {
+        a.x <= b.right() + piece_w / 2 && b.x <= a.right() + piece_w / 2 &&
+        a.y <= b.bottom() + piece_h / 2 && b.y <= a.bottom() + piece_h / 2
+    };
+    let mut merged = true;
+    while merged {
+        merged = false;
+        'merge: for i in 0..groups.len() {
+            for j in i+1..groups.len() {
+                if near(&groups[i], &groups[j]) {
+                    let (a, b) = (groups[i], groups.swap_remove(j));
+                    let (x, y) = (a.x.min(b.x), a.y.min(b.y));
+                    groups[i] = Rect { x, y, width: a.right().max(b.right()) - x, height: a.bottom().max(b.bottom()) - y };
+                    merged = true;
+                    break 'merge;
+                }
+            }
+        }
+    }
+    groups.sort_by_key(|rect| (rect.x, rect.y));
+    let pieces: Vec<Array2<bool>> = groups.iter().map(|group| {
+        let r = ((group.height + piece_h / 2) / piece_h).max(1);
+        let c = ((group.width + piece_w / 2) / piece_w).max(1);
+        Array::from_shape_fn((r, c), |(i, j)| {
+            let (x, y) = (group.x + j * group.width / c, group.y + i * group.height / r);
+            filled(&image, &Rect { x, y, width: group.width / c, height: group.height / r }, calibration.threshold)
+        })
+    }).collect();
+    debug!(?groups, ?pieces);
+    if pieces.is_empty() {
+        return Err(ImportError::NoPieces);
+    }
+    Ok(Screenshot { board, pieces, grid })
+}
@@ -0,0 +1,522 @@
+use crate::{Available, CancelToken, Stats};
+use crate::board::Board;
+use crate::permutation::Permutation;
+use crate::scoring::{Combo, Scoring};
+use crate::eval::{Evaluator, Weights};
+use crate::table::Table;
+use tracing::*;
+use core::cmp::Reverse;
+use core::ops::Range;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::iter::repeat_with;
+use std::{panic, thread};
+use std::sync::{Mutex, PoisonError};
+use std::time::Instant;
+/// Everything about the pieces and the objective the search needs besides the board.
+#[derive(Debug)]
+pub struct Params<'params> {
+    pub piece_avail: &'params [Available],
+    pub piece_cells: &'params [usize],
+    /// `shapes[i]` is the index of the first piece with the same layout as piece `i`.
+    pub shapes: &'params [usize],
+    pub sjt: bool,
+    pub scoring: &'params dyn Scoring,
+    pub combo: Combo,
+    /// Ranks sequences of equal score by their final board.
+    pub tiebreak: Option<Weights>,
+    /// How many of the best sequences with distinct final boards to return.
+    pub keep: usize,
+    /// Worker threads, the search runs on the calling thread when at most 1.
+    pub threads: usize,
+    /// Memory of the transposition tables of all workers together, none when zero.
+    pub table_bytes: usize,
+    pub stop: &'params Stop,
+}
+/// Placements a worker tries between two looks at the limits.
+const CHECK_EVERY: usize = 1024;
+/// Limits of a search, shared by its workers.
+#[derive(Debug)]
+pub struct Stop {
+    deadline: Option<Instant>,
+    node_limit: Option<usize>,
+    cancel: CancelToken,
+    /// Placements between two checks, fewer than [`CHECK_EVERY`] for a small node limit.
+    every: usize,
+    /// Placements reported by the workers so far.
+    nodes: AtomicUsize,
+    stopped: AtomicBool,
+}
+impl Stop {
+    pub fn new(deadline: Option<Instant>, node_limit: Option<usize>, cancel: CancelToken) -> Self {
+        let every = node_limit.map_or(CHECK_EVERY, |limit| limit.clamp(1, CHECK_EVERY));
+        Self { deadline, node_limit, cancel, every, nodes: AtomicUsize::new(0), stopped: AtomicBool::new(false) }
+    }
+    /// Reports `nodes` more placements tried and tells whether the search has to stop.
+    fn check(&self, nodes: usize) -> bool {
+        let total = self.nodes.fetch_add(nodes, Ordering::Relaxed) + nodes;
+        let hit = self.stopped.load(Ordering::Relaxed) || self.cancel.is_cancelled() ||
+                  self.node_limit.is_some_and(|limit| total >= limit) || self.deadline.is_some_and(|deadline| Instant::now() >= deadline);
+        if hit {
+            self.stopped.store(true, Ordering::Relaxed);
+        }
+        hit
+    }
+    /// Whether a limit or the cancellation cut the search short.
+    pub fn stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+}
+/// One of the best sequences found by [`search`], steps in placement order.
+#[derive(Debug)]
+pub struct Best<S> {
+    pub score: usize,
+    /// Index of the piece order among the distinct permutations, in the order they are generated.
+    pub perm: usize,
+    pub place_order: Vec<usize>,
+    pub piece_order: Vec<usize>,
+    pub lines_cleared: Vec<usize>,
+    pub scores: Vec<usize>,
+    pub combo: Combo,
+    pub quality: Option<i64>,
+    pub states: Vec<S>,
+}
+/// Whether `perm` is the first of the orders that only swap identical pieces around, i.e. the
+/// copies of each shape appear by increasing input index.
+fn is_distinct(perm: &[usize], shapes: &[usize], last: &mut [Option<usize>]) -> bool {
+    last.fill(None);
+    for &idx in perm {
+        let shape = shapes[idx];
+        if last[shape].is_some_and(|prev| prev > idx) {
+            return false;
+        }
+        last[shape] = Some(idx);
+    }
+    true
+}
+/// Distinct piece orders, generated one at a time in the order of [`Permutation`].
+struct Orders<'orders> {
+    permutation: Permutation,
+    shapes: &'orders [usize],
+    last_of_shape: Vec<Option<usize>>,
+    /// Distinct orders returned so far.
+    count: usize,
+    skipped: usize,
+    started: bool,
+    done: bool,
+}
+impl<'orders> Orders<'orders> {
+    fn new(params: &Params<'orders>) -> Self {
+        let n_pieces = params.piece_avail.len();
+        let permutation = Permutation::new(n_pieces, params.sjt);
+        debug!("{:?}", permutation);
+        Self { permutation, shapes: params.shapes, last_of_shape: vec![None; n_pieces], count: 0, skipped: 0, started: false, done: false }
+    }
+    /// Moves to the next distinct order and returns its index, or `None` once every order was
+    /// returned or the search has to stop.
+    fn advance(&mut self, stop: &Stop) -> Option<usize> {
+        while !self.done {
+            if self.started && !self.permutation.permute() {
+                self.done = true;
+                break;
+            }
+            self.started = true;
+            if is_distinct(self.permutation.perm(), self.shapes, &mut self.last_of_shape) {
+                self.count += 1;
+                return Some(self.count - 1);
+            }
+            self.skipped += 1;
+            // Runs of duplicates get long with many identical pieces
+            if self.skipped.is_multiple_of(CHECK_EVERY) && stop.check(0) {
+                self.done = true;
+            }
+        }
+        debug!(skipped = ?self.skipped, "Duplicate permutations skipped");
+        None
+    }
+    fn perm(&self) -> &[usize] {
+        self.permutation.perm()
+    }
+}
+/// Work handed out to the threads: the anchors of the first piece of every distinct order in turn,
+/// in the order a single thread searches them.
+struct Feed<'feed> {
+    orders: Orders<'feed>,
+    index: usize,
+    pos: usize,
+    avail_len: usize,
+}
+impl Feed<'_> {
+    /// Index and pieces of the next order with the anchor of its first piece to search.
+    fn next_item(&mut self, params: &Params<'_>) -> Option<(usize, Vec<usize>, usize)> {
+        if self.pos == self.avail_len {
+            let Some(index) = self.orders.advance(params.stop) else {
+                return None;
+            };
+            self.index = index;
+            self.pos = 0;
+            self.avail_len = params.piece_avail[self.orders.perm()[0]].1;
+        }
+        self.pos += 1;
+        Some((self.index, self.orders.perm().to_vec(), self.pos - 1))
+    }
+}
+/// Working state of one depth-first search over piece orders, keeping its own best sequences.
+struct Worker<'worker, B: Board> {
+    params: &'worker Params<'worker>,
+    initial: &'worker B::State,
+    evaluator: Evaluator,
+    // Without a tie-breaker a branch that can only match the worst kept score is cut too
+    tie_slack: usize,
+    total_cells: usize,
+    placed_pieces: usize,
+    total_score: usize,
+    working_place_order: Vec<usize>,
+    working_lines_cleared: Vec<usize>,
+    working_scores: Vec<usize>,
+    working_combos: Vec<Combo>,
+    working_state: Vec<B::State>,
+    cells_left: Vec<usize>,
+    /// Best score of the remaining placements found under the position at every depth so far,
+    /// counting the bounds of cut branches.
+    reach: Vec<Option<usize>>,
+    table: Table<B::State>,
+    /// Table identifiers of the shapes left at every depth of the current order.
+    suffixes: Vec<usize>,
+    best: Vec<Best<B::State>>,
+    stats: Stats,
+    /// Placements not yet reported to the [`Stop`].
+    unchecked: usize,
+}
+impl<'worker, B: Board> Worker<'worker, B> {
+    fn new(params: &'worker Params<'worker>, initial: &'worker B::State, table_capacity: usize) -> Self {
+        let n_pieces = params.piece_avail.len();
+        Self {
+            params,
+            initial,
+            evaluator: Evaluator::default(),
+            tie_slack: usize::from(params.tiebreak.is_some()),
+            total_cells: params.piece_cells.iter().sum(),
+            placed_pieces: 0,
+            total_score: 0,
+            working_place_order: vec![0; n_pieces],
+            working_lines_cleared: vec![0; n_pieces],
+            working_scores: vec![0; n_pieces],
+            working_combos: vec![params.combo; n_pieces+1],
+            working_state: vec![initial.clone(); n_pieces+1],
+            cells_left: vec![0; n_pieces+1],
+            reach: vec![None; n_pieces+1],
+            table: Table::new(table_capacity),
+            suffixes: vec![0; n_pieces+1],
+            best: Vec::with_capacity(params.keep + 1),
+            stats: Stats::default(),
+            unchecked: 0,
+        }
+    }
+    fn bound(&self, board: &mut B, state: &B::State, combo: Combo, pieces: usize, cells: usize) -> usize {
+        self.params.scoring.bound(combo, pieces, cells, board.clear_bound(state, cells))
+    }
+    /// Whether a branch bounded by `bound` cannot beat the worst sequence kept here, or for sure
+    /// not the `floor` shared with the other workers.
+    fn hopeless(&self, bound: usize, floor: &AtomicUsize) -> bool {
+        let keep = self.params.keep;
+        (self.best.len() == keep && bound + self.tie_slack <= self.best[keep-1].score) || bound < floor.load(Ordering::Relaxed)
+    }
+    /// Searches the sequences of the piece order `perm` (the `index`-th distinct one) whose first
+    /// piece is at an anchor in `first`. Returns false once no order can beat the worst sequence
+    /// kept, or when the search has to stop.
+    fn blast(&mut self, board: &mut B, index: usize, perm: &[usize], first: Range<usize>, floor: &AtomicUsize) -> bool {
+        let Params { piece_avail, piece_cells, scoring, tiebreak, keep, .. } = *self.params;
+        let n_pieces = piece_avail.len();
+        let root = self.bound(board, self.initial, self.params.combo, n_pieces, self.total_cells);
+        if self.hopeless(root, floor) {
+            return false;
+        }
+        for i in (0..n_pieces).rev() {
+            self.cells_left[i] = self.cells_left[i+1] + piece_cells[perm[i]];
+        }
+        if self.table.enabled() {
+            let shapes: Vec<usize> = perm.iter().map(|&piece| self.params.shapes[piece]).collect();
+            for i in 0..=n_pieces {
+                self.suffixes[i] = self.table.suffix(&shapes[i..]);
+            }
+        }
+        self.working_place_order[0] = first.start;
+        self.reach[0] = None;
+        'outer: loop {
+            {
+                let piece_idx = perm[self.placed_pieces];
+                let Available(_, avail_len) = piece_avail[piece_idx];
+                let end = if self.placed_pieces == 0 {first.end.min(avail_len)} else {avail_len};
+                loop {
+                    let pos = self.working_place_order[self.placed_pieces];
+                    if pos >= end {
+                        self.working_place_order[self.placed_pieces] = 0;
+                        let depth = self.placed_pieces;
+                        if depth == 0 {
+                            return true;
+                        }
+                        if self.table.enabled() {
+                            let key = (self.working_state[depth].clone(), self.suffixes[depth], self.working_combos[depth]);
+                            self.table.insert(key, self.reach[depth]);
+                        }
+                        self.placed_pieces -= 1;
+                        let parent = self.placed_pieces;
+                        self.reach[parent] = self.reach[parent].max(self.reach[depth].map(|reach| reach + self.working_scores[parent]));
+                        self.total_score -= self.working_scores[self.placed_pieces];
+                        self.working_place_order[self.placed_pieces] += 1;
+                        continue 'outer;
+                    }
+                    let (src, dst) = self.working_state.split_at_mut(self.placed_pieces+1);
+                    if let Some(clear) = board.place(&src[self.placed_pieces], &mut dst[0], piece_idx, pos) {
+                        self.stats.nodes += 1;
+                        self.unchecked += 1;
+                        if self.unchecked == self.params.stop.every {
+                            self.unchecked = 0;
+                            if self.params.stop.check(self.params.stop.every) {
+                                return false;
+                            }
+                        }
+                        let mut next_combo = self.working_combos[self.placed_pieces];
+                        let score = scoring.score(&mut next_combo, piece_cells[piece_idx], clear);
+                        self.working_combos[self.placed_pieces+1] = next_combo;
+                        self.working_lines_cleared[self.placed_pieces] = clear;
+                        self.working_scores[self.placed_pieces] = score;
+                        self.total_score += score;
+                        break;
+                    }
+                    self.working_place_order[self.placed_pieces] += 1;
+                }
+            }
+            if self.placed_pieces + 1 == n_pieces {
+                // The first complete placements are kept even without a score
+                let total_score = self.total_score;
+                let depth = self.placed_pieces;
+                self.reach[depth] = self.reach[depth].max(Some(self.working_scores[depth]));
+                if self.best.len() < keep || total_score + self.tie_slack > self.best[keep-1].score {
+                    let last = &self.working_state[n_pieces];
+                    let quality = tiebreak.map(|weights| weights.value(&self.evaluator.evaluate(&board.store(last))));
+                    let rank = (total_score, quality);
+                    let dup = self.best.iter().position(|b| b.states.last() == Some(last));
+                    if dup.is_none_or(|i| rank > (self.best[i].score, self.best[i].quality)) {
+                        if let Some(i) = dup {
+                            self.best.remove(i);
+                        }
+                        let at = self.best.iter().position(|b| (b.score, b.quality) < rank).unwrap_or(self.best.len());
+                        self.best.insert(at, Best {
+                            score: total_score,
+                            perm: index,
+                            place_order: self.working_place_order.clone(),
+                            piece_order: perm.to_vec(),
+                            lines_cleared: self.working_lines_cleared.clone(),
+                            scores: self.working_scores.clone(),
+                            combo: self.working_combos[n_pieces],
+                            quality,
+                            states: self.working_state[1..].to_vec(),
+                        });
+                        self.best.truncate(keep);
+                        if self.best.len() == keep {
+                            floor.fetch_max(self.best[keep-1].score, Ordering::Relaxed);
+                        }
+                    }
+                }
+                self.total_score -= self.working_scores[self.placed_pieces];
+                self.working_place_order[self.placed_pieces] += 1;
+                continue;
+            }
+            let next = self.placed_pieces + 1;
+            let known = if self.table.enabled() {
+                self.stats.table_lookups += 1;
+                self.table.get(&(self.working_state[next].clone(), self.suffixes[next], self.working_combos[next]))
+            } else {
+                None
+            };
+            let bound = if let Some(&reach) = known {
+                self.stats.table_hits += 1;
+                reach
+            } else {
+                Some(self.bound(board, &self.working_state[next], self.working_combos[next], n_pieces - next, self.cells_left[next]))
+            };
+            // A position known to have no way to place the rest is cut too
+            if bound.is_none_or(|rest| self.hopeless(self.total_score + rest, floor)) {
+                let depth = self.placed_pieces;
+                self.reach[depth] = self.reach[depth].max(bound.map(|rest| rest + self.working_scores[depth]));
+                self.stats.pruned += 1;
+                self.total_score -= self.working_scores[self.placed_pieces];
+                self.working_place_order[self.placed_pieces] += 1;
+                continue;
+            }
+            self.placed_pieces += 1;
+            self.reach[next] = None;
+        }
+    }
+}
+/// Exhaustive search over the orders and anchors of the pieces for the highest score.
+///
+/// Orders of identical pieces are only tried once, and branches whose [`Scoring::bound`] on top of
+/// the [`Board::clear_bound`] cannot beat the best sequence so far are cut. With a tie-breaker,
+/// sequences of equal score are ranked by the [`Weights::value`] of their final board, otherwise
+/// the first one found is kept. Sequences ending on the same board as a better one are dropped,
+/// and the `keep` best of the rest are returned from best to worst.
+///
+/// Positions reached again with the same shapes left in the same order are cut by the best score
+/// found under them the first time, up to the capacity of the transposition table.
+///
+/// With several threads, the workers take the anchors of the first piece of every order in turn
+/// and only share the lowest score worth finding, so a branch is cut for another worker's result
+/// only when it is strictly worse. Merging the workers' sequences by rank, then by the order a
+/// single thread finds them in, gives the same result as a single thread.
+#[instrument(skip_all)]
+pub fn search<B: Board>(board: &mut B, initial: &B::State, params: &Params<'_>) -> (Vec<Best<B::State>>, Stats) {
+    debug!(n_pieces = ?params.piece_avail.len(), piece_avail = ?params.piece_avail, shapes = ?params.shapes, scoring = ?params.scoring);
+    let mut orders = Orders::new(params);
+    let entry_bytes = board.state_bytes(initial) + size_of::<(usize, Combo, Option<usize>)>() + 1;
+    let table_capacity = params.table_bytes / params.threads.max(1) / entry_bytes;
+    // Nothing is shared with a single worker, so the floor never rises
+    let floor = AtomicUsize::new(0);
+    if params.threads <= 1 {
+        let mut worker = Worker::new(params, initial, table_capacity);
+        while let Some(index) = orders.advance(params.stop) {
+            if !worker.blast(board, index, orders.perm(), 0..usize::MAX, &floor) {
+                if params.stop.stopped() {
+                    debug!(perm = ?orders.perm(), "Search stopped");
+                } else {
+                    debug!(perm = ?orders.perm(), "No order left can score more");
+                }
+                break;
+            }
+        }
+        debug!(stats = ?worker.stats, table = worker.table.len(), "Search finished");
+        return (worker.best, worker.stats);
+    }
+    let feed = &Mutex::new(Feed { orders, index: 0, pos: 0, avail_len: 0 });
+    let shared_floor = &floor;
+    let workers: Vec<Worker<'_, B>> = thread::scope(|scope| {
+        let handles: Vec<_> = repeat_with(|| board.clone()).take(params.threads).map(|mut worker_board| {
+            scope.spawn(move || {
+                let mut worker = Worker::new(params, initial, table_capacity);
+                // Items are taken in increasing order, so every worker sees its sequences in the
+                // order a single thread would
+                loop {
+                    let item = feed.lock().unwrap_or_else(PoisonError::into_inner).next_item(params);
+                    let Some((index, perm, pos)) = item else {
+                        break;
+                    };
+                    if !worker.blast(&mut worker_board, index, &perm, pos..pos+1, shared_floor) {
+                        break;
+                    }
+                }
+                worker
+            })
+        }).collect();
+        handles.into_iter().map(|handle| handle.join().unwrap_or_else(|e| panic::resume_unwind(e))).collect()
+    });
+    let mut stats = Stats::default();
+    let mut merged: Vec<Best<B::State>> = Vec::new();
+    for worker in workers {
+        stats.nodes += worker.stats.nodes;
+        stats.pruned += worker.stats.pruned;
+        stats.table_lookups += worker.stats.table_lookups;
+        stats.table_hits += worker.stats.table_hits;
+        merged.extend(worker.best);
+    }
+    merged.sort_by(|a, b| {
+        (Reverse((a.score, a.quality)), a.perm, &a.place_order).cmp(&(Reverse((b.score, b.quality)), b.perm, &b.place_order))
+    });
+    let mut best: Vec<Best<B::State>> = Vec::with_capacity(params.keep);
+    for candidate in merged {
+        if best.len() < params.keep && !best.iter().any(|b| b.states.last() == candidate.states.last()) {
+            best.push(candidate);
+        }
+    }
+    debug!(threads = ?params.threads, ?stats, "Search finished");
+    (best, stats)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rules;
+    use crate::bitboard::BitBoard;
+    use crate::catalogue::Catalogue;
+    use crate::scoring::{LinesCleared, Points};
+    use ndarray::prelude::*;
+    /// Score, quality, piece order, anchors and final board of every sequence returned.
+    type Outcome = (usize, Option<i64>, Vec<usize>, Vec<usize>, Array2<bool>);
+    /// Settings of one search of [`outcomes`] besides the puzzle.
+    #[derive(Clone, Copy)]
+    struct Setup<'setup> {
+        scoring: &'setup dyn Scoring,
+        tiebreak: Option<Weights>,
+        keep: usize,
+        threads: usize,
+        table_bytes: usize,
+        /// Skip the orders that only swap identical pieces around.
+        skip: bool,
+    }
+    const PLAIN: Setup<'static> = Setup { scoring: &LinesCleared, tiebreak: None, keep: 1, threads: 1, table_bytes: 0, skip: true };
+    fn board(rows: &[&str]) -> Array2<bool> {
+        Array::from_shape_fn((rows.len(), rows[0].len()), |(i, j)| rows[i].as_bytes()[j] == b'#')
+    }
+    fn outcomes(grid: &Array2<bool>, names: &[&str], setup: Setup<'_>) -> Vec<Outcome> {
+        let catalogue = Catalogue::standard();
+        let pieces: Vec<Array2<bool>> = names.iter().map(|&name| catalogue.get(name).unwrap().layout.clone()).collect();
+        let (rows, cols) = grid.dim();
+        let piece_avail: Vec<Available> = pieces.iter().map(|layout| Available::new(rows, cols, layout.nrows(), layout.ncols())).collect();
+        let piece_cells: Vec<usize> = pieces.iter().map(|layout| layout.iter().filter(|&&k| k).count()).collect();
+        let shapes: Vec<usize> = (0..pieces.len()).map(|i| if setup.skip {pieces.iter().position(|other| *other == pieces[i]).unwrap()} else {i}).collect();
+        let stop = Stop::new(None, None, CancelToken::default());
+        let params = Params {
+            piece_avail: &piece_avail, piece_cells: &piece_cells, shapes: &shapes, sjt: false, scoring: setup.scoring,
+            combo: Combo::default(), tiebreak: setup.tiebreak, keep: setup.keep, threads: setup.threads, table_bytes: setup.table_bytes, stop: &stop,
+        };
+        let mut bits = BitBoard::<1>::new(rows, cols, &pieces, &piece_avail, &Rules::default());
+        let initial = bits.load(grid);
+        let (best, _) = search(&mut bits, &initial, &params);
+        best.into_iter().map(|b| (b.score, b.quality, b.piece_order, b.place_order, bits.store(b.states.last().unwrap()))).collect()
+    }
+    const BOARDS: [[&str; 6]; 2] = [
+        ["##.###", "#....#", "..##..", "......", "##..##", ".####."],
+        ["#####.", "####..", "###...", "##....", "#.....", "......"],
+    ];
+    const PIECES: [&str; 4] = ["I2h", "I2h", "L3", "I3v"];
+    #[test]
+    fn threads_match_one_thread() {
+        for rows in BOARDS {
+            let grid = board(&rows);
+            for (tiebreak, keep) in [(None, 1), (Some(Weights::default()), 1), (None, 5), (Some(Weights::default()), 5)] {
+                let one = Setup { tiebreak, keep, ..PLAIN };
+                let four = Setup { threads: 4, ..one };
+                assert_eq!(outcomes(&grid, &PIECES, four), outcomes(&grid, &PIECES, one), "{rows:?} with {tiebreak:?}, keeping {keep}");
+            }
+        }
+    }
+    #[test]
+    fn table_matches_plain_search() {
+        let points = Points::default();
+        let cases: [(&dyn Scoring, Option<Weights>, usize); 3] = [(&LinesCleared, None, 1), (&points, None, 1), (&LinesCleared, Some(Weights::default()), 5)];
+        for rows in BOARDS {
+            let grid = board(&rows);
+            for (scoring, tiebreak, keep) in cases {
+                let plain = Setup { scoring, tiebreak, keep, ..PLAIN };
+                let table = Setup { table_bytes: 1 << 20, ..plain };
+                assert_eq!(outcomes(&grid, &PIECES, table), outcomes(&grid, &PIECES, plain), "{rows:?} with {scoring:?}, keeping {keep}");
+            }
+        }
+    }
+    #[test]
+    fn skipped_orders_match_plain_search() {
+        // Swapping identical pieces changes which of the equal sequences comes first, so only the
+        // ranked scores and the final boards of every outcome are compared
+        let summary = |mut found: Vec<Outcome>| {
+            let scores: Vec<(usize, Option<i64>)> = found.iter().map(|outcome| (outcome.0, outcome.1)).collect();
+            found.sort_by(|a, b| a.4.iter().cmp(b.4.iter()));
+            (scores, found.into_iter().map(|outcome| outcome.4).collect::<Vec<_>>())
+        };
+        for rows in BOARDS {
+            let grid = board(&rows);
+            // Keeping every distinct final board leaves no tie to break at the cut
+            let skip = Setup { keep: 50_000, ..PLAIN };
+            let every = Setup { skip: false, ..skip };
+            assert_eq!(summary(outcomes(&grid, &PIECES[..3], skip)), summary(outcomes(&grid, &PIECES[..3], every)), "{rows:?}");
+        }
+    }
+}
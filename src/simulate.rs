@@ -0,0 +1,186 @@
+use crate::{BlockBlastPuzzle, Rules, Solution, SolveError, SolveOptions, Stats, Step, solve_with};
+use crate::catalogue::Catalogue;
+use crate::rng::Rng;
+use crate::scoring::Combo;
+use tracing::*;
+use ndarray::prelude::*;
+use core::fmt;
+use core::iter::repeat_with;
+use serde::{Deserialize, Serialize};
+/// Picks the moves of a round.
+pub trait Strategy: fmt::Debug + Send + Sync {
+    /// Placements of every piece of `puzzle`.
+    ///
+    /// # Errors
+    /// [`SolveError::Unsolvable`] when the strategy finds no way to place every piece.
+    fn play(&self, puzzle: &BlockBlastPuzzle) -> Result<Solution, SolveError>;
+    /// Whether the moves are being cut short, see [`SolveOptions::cancel`].
+    fn cancelled(&self) -> bool;
+}
+/// The full search of [`solve_with`].
+impl Strategy for SolveOptions {
+    fn play(&self, puzzle: &BlockBlastPuzzle) -> Result<Solution, SolveError> {
+        solve_with(puzzle, self)
+    }
+    fn cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+}
+/// Places the pieces one at a time in the order they were dealt, each at its best position
+/// for that piece alone.
+#[derive(Clone, Debug, Default)]
+pub struct Greedy(pub SolveOptions);
+impl Strategy for Greedy {
+    fn play(&self, puzzle: &BlockBlastPuzzle) -> Result<Solution, SolveError> {
+        let mut board = puzzle.board.clone();
+        let mut total = Solution { clears: 0, score: 0, combo: puzzle.combo, quality: None, outlook: None,
+                                   optimal: true, stats: Stats::default(), steps: Vec::with_capacity(puzzle.pieces.len()) };
+        for (piece, layout) in puzzle.pieces.iter().enumerate() {
+            let single = BlockBlastPuzzle { board, pieces: vec![layout.clone()], rules: puzzle.rules.clone(), combo: total.combo, fixed: Vec::new() };
+            let solution = match solve_with(&single, &self.0) {
+                Ok(solution) => solution,
+                Err(e) => return Err(e),
+            };
+            total.clears += solution.clears;
+            total.score += solution.score;
+            total.combo = solution.combo;
+            total.optimal &= solution.optimal;
+            total.stats.nodes += solution.stats.nodes;
+            total.stats.pruned += solution.stats.pruned;
+            board = single.board;
+            for step in solution.steps {
+                board.clone_from(&step.board);
+                total.steps.push(Step { piece, ..step });
+            }
+        }
+        Ok(total)
+    }
+    fn cancelled(&self) -> bool {
+        self.0.cancel.is_cancelled()
+    }
+}
+/// Game played by [`simulate`].
+#[derive(Clone, Debug)]
+pub struct Simulation {
+    pub rows: usize,
+    pub cols: usize,
+    pub rules: Rules,
+    /// Distribution of the dealt pieces.
+    pub catalogue: Catalogue,
+    /// Pieces per deal.
+    pub deal: usize,
+    pub games: usize,
+    pub seed: u64,
+    /// Stop a game after this many rounds even if it could go on.
+    pub max_rounds: Option<usize>,
+}
+impl Default for Simulation {
+    fn default() -> Self {
+        Self { rows: 8, cols: 8, rules: Rules::default(), catalogue: Catalogue::standard(), deal: 3, games: 100, seed: 0, max_rounds: None }
+    }
+}
+/// Outcome of one simulated game.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Game {
+    /// Rounds whose pieces were all placed.
+    pub rounds: usize,
+    pub clears: usize,
+    pub score: usize,
+    /// Every round was played from a solution proven optimal, see [`Solution::optimal`].
+    pub optimal: bool,
+}
+/// Plays [`Simulation::games`] games, dealing random pieces every round until a deal cannot be
+/// placed.
+///
+/// Every game draws its deals from its own generator seeded from [`Simulation::seed`], so
+/// the results only depend on the simulation and the strategy. When the strategy is cancelled or
+/// interrupted, the games finished so far are returned and the one being played is dropped.
+///
+/// # Errors
+/// Returns the error of the strategy when a round is malformed, for example a board too large for
+/// the requested backend. A deal that cannot be placed ends the game instead.
+#[instrument(skip_all)]
+pub fn simulate(simulation: &Simulation, strategy: &dyn Strategy) -> Result<Vec<Game>, SolveError> {
+    let weights: Vec<u64> = simulation.catalogue.entries.iter().map(|entry| u64::from(entry.weight)).collect();
+    if weights.iter().sum::<u64>() == 0 {
+        return Err(SolveError::NoPieces);
+    }
+    let mut seeds = Rng::new(simulation.seed);
+    let mut games = Vec::with_capacity(simulation.games);
+    for index in 0..simulation.games {
+        let mut rng = Rng::new(seeds.next_u64());
+        let mut game = Game { optimal: true, ..Game::default() };
+        let mut board: Array2<bool> = Array::from_elem((simulation.rows, simulation.cols), false);
+        let mut combo = Combo::default();
+        while simulation.max_rounds.is_none_or(|max| game.rounds < max) {
+            let pieces = repeat_with(|| rng.weighted(&weights)).take(simulation.deal)
+                .map(|i| simulation.catalogue.entries[i].layout.clone()).collect();
+            let puzzle = BlockBlastPuzzle { board, pieces, rules: simulation.rules.clone(), combo, fixed: Vec::new() };
+            let solution = match strategy.play(&puzzle) {
+                // A search cut short may have played worse than it could, so the game is dropped
+                _ if strategy.cancelled() => {
+                    debug!(?index, "Cancelled");
+                    return Ok(games);
+                },
+                Ok(solution) => solution,
+                Err(SolveError::Unsolvable | SolveError::EmptyPiece(_) | SolveError::InvalidPiece { .. }) => break,
+                Err(SolveError::Interrupted) => {
+                    debug!(?index, "Interrupted");
+                    return Ok(games);
+                },
+                Err(e) => return Err(e),
+            };
+            game.rounds += 1;
+            game.clears += solution.clears;
+            game.score += solution.score;
+            game.optimal &= solution.optimal;
+            combo = solution.combo;
+            board = solution.steps.last().map_or(puzzle.board, |step| step.board.clone());
+        }
+        debug!(?index, ?game);
+        games.push(game);
+    }
+    Ok(games)
+}
+/// Spread of one quantity over the simulated games.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Spread {
+    pub min: usize,
+    /// First quartile.
+    pub lower: usize,
+    pub median: usize,
+    /// Third quartile.
+    pub upper: usize,
+    pub max: usize,
+    pub total: usize,
+}
+impl Spread {
+    /// Spread of `values`, all zero when there are none.
+    #[must_use]
+    pub fn of(mut values: Vec<usize>) -> Self {
+        values.sort_unstable();
+        let Some(&max) = values.last() else {
+            return Self::default();
+        };
+        let at = |q: usize| values[(values.len() - 1) * q / 4];
+        Self { min: values[0], lower: at(1), median: at(2), upper: at(3), max, total: values.iter().sum() }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn same_seed_same_games() {
+        let simulation = Simulation { games: 4, seed: 42, max_rounds: Some(20), ..Simulation::default() };
+        let options = SolveOptions::default();
+        let first = simulate(&simulation, &options).unwrap();
+        assert_eq!(first.len(), 4, "every game is played");
+        assert_eq!(first, simulate(&simulation, &options).unwrap(), "runs with the same seed differ");
+    }
+    #[test]
+    fn spread_quartiles() {
+        let spread = Spread::of(vec![9, 1, 7, 3, 5]);
+        assert_eq!(spread, Spread { min: 1, lower: 3, median: 5, upper: 7, max: 9, total: 25 }, "spread of 1 to 9 by 2");
+        assert_eq!(Spread::of(Vec::new()), Spread::default(), "spread of nothing");
+    }
+}
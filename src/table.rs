@@ -0,0 +1,46 @@
+use crate::scoring::Combo;
+use rustc_hash::FxHashMap;
+use core::hash::Hash;
+/// Upper bounds on the score still reachable from positions the search has finished, so that a
+/// position reached again through another order of the same pieces can be cut without searching.
+///
+/// Positions are keyed on the board, the shapes left to place in order and the combo state, the
+/// value is the best score of the remaining placements, or `None` when they cannot all be placed.
+#[derive(Debug)]
+pub struct Table<S> {
+    entries: FxHashMap<(S, usize, Combo), Option<usize>>,
+    /// Interned sequences of remaining shapes.
+    suffixes: FxHashMap<Vec<usize>, usize>,
+    capacity: usize,
+}
+impl<S: Eq + Hash> Table<S> {
+    /// A table holding at most `capacity` positions, disabled when zero.
+    pub fn new(capacity: usize) -> Self {
+        Self { entries: FxHashMap::default(), suffixes: FxHashMap::default(), capacity }
+    }
+    pub const fn enabled(&self) -> bool {
+        self.capacity > 0
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    /// Identifier of the sequence of remaining shapes `suffix`.
+    pub fn suffix(&mut self, suffix: &[usize]) -> usize {
+        if let Some(&id) = self.suffixes.get(suffix) {
+            return id;
+        }
+        let id = self.suffixes.len();
+        self.suffixes.insert(suffix.to_vec(), id);
+        id
+    }
+    pub fn get(&self, key: &(S, usize, Combo)) -> Option<&Option<usize>> {
+        self.entries.get(key)
+    }
+    /// Records `reach` for `key` unless the table is full. Positions are never evicted, since
+    /// every one of them stays valid until the end of the search.
+    pub fn insert(&mut self, key: (S, usize, Combo), reach: Option<usize>) {
+        if self.entries.len() < self.capacity || self.entries.contains_key(&key) {
+            self.entries.insert(key, reach);
+        }
+    }
+}